@@ -1,10 +1,22 @@
-// [3 cell][5 cell][counter cell][temp cell][]
+// named cells, a bare name moves the pointer to its cell
+three: 0
+five: 1
+counter: 2
+temp: 3
 
-[3+] . >
-[5+] . >
-[10+] .
+three [3+] .
+five [5+] .
+counter [10+] .
+
+// {1: ...} declares function 1, {name: ...} declares a named function,
+// the callee gets its own tape with the arguments in cells 0, 1, ...
+// and returns the cell it ends on
+{inc: + }
 
 // [1+]&(>>, >) >>;
 // will call function 1,
 // and have arguments of cell 2 and cell 1,
-// the return will be stored in cell 2
\ No newline at end of file
+// the return will be stored in cell 2
+
+&inc(five) temp;
+temp .
@@ -0,0 +1,280 @@
+use std::cmp::Ordering;
+use std::fmt;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use crate::Int;
+
+// f64 cells hold every integer up to this exactly
+const F64_EXACT: Int = 1 << 53;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CellType {
+    U8,
+    U16,
+    U32,
+    I32,
+    I64,
+    // arbitrary precision
+    Big,
+    // the only type that holds floats, it never overflows
+    F64,
+}
+
+impl CellType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "u8" => Some(CellType::U8),
+            "u16" => Some(CellType::U16),
+            "u32" => Some(CellType::U32),
+            "i32" => Some(CellType::I32),
+            "i64" => Some(CellType::I64),
+            "big" => Some(CellType::Big),
+            "f64" => Some(CellType::F64),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellType::U8 => "u8",
+            CellType::U16 => "u16",
+            CellType::U32 => "u32",
+            CellType::I32 => "i32",
+            CellType::I64 => "i64",
+            CellType::Big => "big",
+            CellType::F64 => "f64",
+        }
+    }
+    // smallest and largest integer a cell holds, None when unbounded or a float
+    pub fn bounds(&self) -> Option<(i128, i128)> {
+        match self {
+            CellType::U8 => Some((0, u8::MAX as i128)),
+            CellType::U16 => Some((0, u16::MAX as i128)),
+            CellType::U32 => Some((0, u32::MAX as i128)),
+            CellType::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            CellType::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            CellType::Big | CellType::F64 => None,
+        }
+    }
+    pub fn contains(&self, value: Int) -> bool {
+        match self.bounds() {
+            Some((min, max)) => min <= value as i128 && value as i128 <= max,
+            None if *self == CellType::F64 => value.unsigned_abs() <= F64_EXACT as u64,
+            None => true,
+        }
+    }
+
+    // brings `value` into range, None when it does not fit and the policy is `Overflow::Error`
+    pub fn from_int(&self, overflow: Overflow, value: i128) -> Option<Cell> {
+        let (min, max) = match self.bounds() {
+            Some(bounds) => bounds,
+            None if *self == CellType::F64 => return Some(Cell::Float(value as f64)),
+            None => return Some(Cell::from(BigInt::from(value))),
+        };
+        if min <= value && value <= max {
+            return Some(Cell::Int(value as Int));
+        }
+        let value = match overflow {
+            Overflow::Wrap => min + (value - min).rem_euclid(max - min + 1),
+            Overflow::Saturate => value.clamp(min, max),
+            Overflow::Error => return None,
+        };
+        Some(Cell::Int(value as Int))
+    }
+    pub fn from_big(&self, overflow: Overflow, value: BigInt) -> Option<Cell> {
+        match self {
+            CellType::Big => return Some(Cell::from(value)),
+            CellType::F64 => return Some(Cell::Float(value.to_f64().unwrap_or(f64::NAN))),
+            _ => {}
+        }
+        let (min, max) = self.bounds()?;
+        match i128::try_from(&value) {
+            Ok(value) => self.from_int(overflow, value),
+            Err(_) => match overflow {
+                Overflow::Wrap => {
+                    let size = BigInt::from(max - min + 1);
+                    let wrapped = ((value - min) % &size + &size) % &size;
+                    self.from_int(overflow, min + i128::try_from(&wrapped).ok()?)
+                }
+                Overflow::Saturate => {
+                    let value = if value.sign() == num_bigint::Sign::Minus { min } else { max };
+                    self.from_int(overflow, value)
+                }
+                Overflow::Error => None,
+            },
+        }
+    }
+    pub fn add(&self, overflow: Overflow, cell: &Cell, amount: Int) -> Option<Cell> {
+        self.apply(overflow, Operator::Add, cell, &Cell::Int(amount))
+    }
+    // `lhs operator rhs`, rhs is never 0 for `Div` and `Mod`, None when the
+    // result does not fit, floats only fit in `CellType::F64`
+    pub fn apply(&self, overflow: Overflow, operator: Operator, lhs: &Cell, rhs: &Cell) -> Option<Cell> {
+        if operator == Operator::Set {
+            return match rhs {
+                Cell::Int(value) => self.from_int(overflow, *value as i128),
+                Cell::Big(value) => self.from_big(overflow, value.as_ref().clone()),
+                Cell::Float(_) if *self == CellType::F64 => Some(rhs.clone()),
+                Cell::Float(_) => None,
+            };
+        }
+        if let (Cell::Float(_), _) | (_, Cell::Float(_)) = (lhs, rhs) {
+            if *self != CellType::F64 {
+                return None;
+            }
+            let (lhs, rhs) = (lhs.to_f64(), rhs.to_f64());
+            return Some(Cell::Float(match operator {
+                Operator::Add => lhs + rhs,
+                Operator::Sub => lhs - rhs,
+                Operator::Mul => lhs * rhs,
+                Operator::Div => lhs / rhs,
+                Operator::Mod => lhs % rhs,
+                Operator::Set => rhs,
+            }));
+        }
+        if let (Cell::Int(lhs), Cell::Int(rhs)) = (lhs, rhs) {
+            // two `Int`s never overflow an i128
+            let (lhs, rhs) = (*lhs as i128, *rhs as i128);
+            let value = match operator {
+                Operator::Add => lhs + rhs,
+                Operator::Sub => lhs - rhs,
+                Operator::Mul => lhs * rhs,
+                Operator::Div => lhs / rhs,
+                Operator::Mod => lhs % rhs,
+                Operator::Set => rhs,
+            };
+            return self.from_int(overflow, value);
+        }
+        let (lhs, rhs) = (lhs.to_big(), rhs.to_big());
+        let value = match operator {
+            Operator::Add => lhs + rhs,
+            Operator::Sub => lhs - rhs,
+            Operator::Mul => lhs * rhs,
+            Operator::Div => lhs / rhs,
+            Operator::Mod => lhs % rhs,
+            Operator::Set => rhs,
+        };
+        self.from_big(overflow, value)
+    }
+}
+
+// division and modulo truncate toward zero
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Set,
+}
+
+impl Operator {
+    pub fn symbol(&self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Sub => '-',
+            Operator::Mul => '*',
+            Operator::Div => '/',
+            Operator::Mod => '%',
+            Operator::Set => '=',
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overflow {
+    Wrap,
+    Saturate,
+    Error,
+}
+
+impl Overflow {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wrap" => Some(Overflow::Wrap),
+            "saturate" => Some(Overflow::Saturate),
+            "error" => Some(Overflow::Error),
+            _ => None,
+        }
+    }
+}
+
+// a value on the tape, `Big` is only used for values outside of `Int`,
+// integers and floats compare by value
+#[derive(Clone, Debug)]
+pub enum Cell {
+    Int(Int),
+    Big(Box<BigInt>),
+    Float(f64),
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell::Int(0)
+    }
+}
+
+impl From<BigInt> for Cell {
+    fn from(value: BigInt) -> Self {
+        match Int::try_from(&value) {
+            Ok(value) => Cell::Int(value),
+            Err(_) => Cell::Big(Box::new(value)),
+        }
+    }
+}
+
+impl Cell {
+    // whole floats count as long as every integer up to them is exact
+    pub fn as_int(&self) -> Option<Int> {
+        match self {
+            Cell::Int(value) => Some(*value),
+            Cell::Float(value) if value.fract() == 0.0 && value.abs() <= F64_EXACT as f64 => Some(*value as Int),
+            Cell::Big(_) | Cell::Float(_) => None,
+        }
+    }
+    pub fn is(&self, value: Int) -> bool {
+        *self == Cell::Int(value)
+    }
+    // floats are truncated
+    fn to_big(&self) -> BigInt {
+        match self {
+            Cell::Int(value) => BigInt::from(*value),
+            Cell::Big(value) => value.as_ref().clone(),
+            Cell::Float(value) => BigInt::from(*value as i128),
+        }
+    }
+    fn to_f64(&self) -> f64 {
+        match self {
+            Cell::Int(value) => *value as f64,
+            Cell::Big(value) => value.to_f64().unwrap_or(f64::NAN),
+            Cell::Float(value) => *value,
+        }
+    }
+}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Cell::Int(lhs), Cell::Int(rhs)) => Some(lhs.cmp(rhs)),
+            (Cell::Float(_), _) | (_, Cell::Float(_)) => self.to_f64().partial_cmp(&other.to_f64()),
+            _ => Some(self.to_big().cmp(&other.to_big())),
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Int(value) => write!(f, "{}", value),
+            Cell::Big(value) => write!(f, "{}", value),
+            // keeps the point so floats stand out, 3.0 rather than 3
+            Cell::Float(value) => write!(f, "{:?}", value),
+        }
+    }
+}
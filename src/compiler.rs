@@ -0,0 +1,988 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use crate::cell::{Cell, CellType, Operator, Overflow};
+use crate::error::{CompileError, CompileErrorKind};
+use crate::lexer::{Span, Token, TokenType};
+use crate::vm::InputMode;
+use crate::Int;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+impl Comparison {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterEqual),
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessEqual),
+            _ => None,
+        }
+    }
+    pub fn test(&self, lhs: &Cell, rhs: &Cell) -> bool {
+        match self {
+            Comparison::Equal => lhs == rhs,
+            Comparison::NotEqual => lhs != rhs,
+            Comparison::Greater => lhs > rhs,
+            Comparison::GreaterEqual => lhs >= rhs,
+            Comparison::Less => lhs < rhs,
+            Comparison::LessEqual => lhs <= rhs,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operand {
+    Value(Int),
+    // `+1.5`, `=3.14`, turns the cell into a float
+    Float(f64),
+    // `@>2`, the cell at this offset from the pointer
+    Cell(isize),
+}
+
+// what a condition checks, `!` binds tighter than `&&`, which binds tighter than `||`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Test {
+    // lhs and rhs are code, each ending on the cell to compare
+    Compare { comparison: Comparison, lhs: Range<usize>, rhs: Range<usize> },
+    Not(Box<Test>),
+    // the right side only runs when it can still change the result
+    And(Box<Test>, Box<Test>),
+    Or(Box<Test>, Box<Test>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Callee {
+    // `&(...)`, the function number is the value of the current cell
+    Cell,
+    // `&name(...)`, resolved to the function body
+    Function(Range<usize>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Instruction {
+    // `+` and `-`, runs are folded into one instruction when optimizing
+    Add(Int),
+    // `>` and `<`, runs are folded into one instruction when optimizing,
+    // `low` and `high` are the furthest the run goes on the way so a folded
+    // `<>` still grows the tape and fails like its steps
+    Move { offset: isize, low: isize, high: isize },
+    // a named cell, moves the pointer to the position
+    MoveTo(isize),
+    // `name: auto`, zeroes the cell given to the name without moving the pointer
+    Declare(isize),
+    // `*5`, `/@>2`, `=1.5` and the like, `+5` and `-5` become `Add`
+    Arithmetic { operator: Operator, operand: Operand },
+    // a clear loop such as `[0 -]`, steps the cell by `step` until it is `until`
+    Clear { until: Int, step: Int },
+    PrintOut,
+    PrintChar,
+    // `$"text"`, prints the text as it is
+    PrintLiteral(String),
+    // a bare `$`, prints the cells from the pointer on as text up to a zero cell
+    PrintText,
+    // None uses the VM's default input mode
+    ReadIn(Option<InputMode>),
+    // a string literal, writes its code points into the cells from the
+    // pointer on, the pointer stays where it was
+    StoreText(String),
+    // `#`, writes the tape around the pointer and the running loops to stderr
+    Dump,
+
+    // `[n ... ]`, jumps past `end` when the cell already equals `until`
+    LoopStart { until: Int, end: usize },
+    // jumps back to the instruction after `start` while the cell is not `until`
+    LoopEnd { until: Int, start: usize },
+
+    // `( lhs "op" rhs ; body )`, the operands of `test` come before `body`,
+    // a false condition continues at `otherwise`, the next `else` branch,
+    // and `end` is the `ConditionEnd` closing every branch
+    Condition { test: Test, body: usize, otherwise: usize, end: usize },
+    // `else`, closes the branch before it, which jumps past `end`
+    Else { end: usize },
+    ConditionEnd,
+
+    // `{name: body}`, skipped when reached, the body runs up to `end`
+    Function { end: usize },
+    FunctionEnd,
+    // `&name(args) target;`, each range is code that selects a cell
+    // relative to the pointer, execution continues at `target.end`
+    Call { callee: Callee, arguments: Vec<Range<usize>>, target: Range<usize> },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    // source position of every instruction
+    pub spans: Vec<Span>,
+    // function number -> instructions of its body
+    pub numbered_functions: HashMap<Int, Range<usize>>,
+    pub named_functions: HashMap<String, Range<usize>>,
+    // `name: 2`, the cells named at the top level, each function body names its own
+    pub named_cells: HashMap<String, isize>,
+    // where every `name: auto` cell ended up
+    pub allocations: Vec<Allocation>,
+    // first instruction of a function body -> most arguments it takes,
+    // only for functions whose `name: auto` cells come right after them
+    pub argument_limits: HashMap<usize, usize>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Allocation {
+    pub name: String,
+    // the function declaring the name, None at the top level
+    pub function: Option<String>,
+    pub position: isize,
+    // instructions from the declaration to the last use, whole loops included
+    pub live: Range<usize>,
+    pub span: Span,
+}
+
+impl Program {
+    // one line per named cell, top level first, ordered by position and
+    // then by when the names sharing a cell are live
+    //
+    // cell 0  counter
+    // cell 1  total, auto, live over instructions 3..9
+    // in inc: cell 1  step, auto, live over instructions 14..16
+    pub fn layout(&self) -> Vec<String> {
+        let live = |function: Option<&str>, name: &str| {
+            self.allocations
+                .iter()
+                .find(|allocation| allocation.function.as_deref() == function && allocation.name == name)
+                .map_or(0, |allocation| allocation.live.start)
+        };
+        let mut cells: Vec<(Option<&str>, isize, usize, &str)> = self
+            .named_cells
+            .iter()
+            .map(|(name, position)| (None, *position, live(None, name), name.as_str()))
+            .collect();
+        let functions = self.allocations.iter().filter(|allocation| allocation.function.is_some());
+        cells.extend(functions.map(|allocation| {
+            let function = allocation.function.as_deref();
+            (function, allocation.position, allocation.live.start, allocation.name.as_str())
+        }));
+        cells.sort();
+
+        let mut lines = vec![];
+        for (function, position, _, name) in cells {
+            let allocation = self
+                .allocations
+                .iter()
+                .find(|allocation| allocation.function.as_deref() == function && allocation.name == name);
+            let mut line = match function {
+                Some(function) => format!("in {}: cell {}  {}", function, position, name),
+                None => format!("cell {}  {}", position, name),
+            };
+            if let Some(allocation) = allocation {
+                line += &format!(", auto, live over instructions {}..{}", allocation.live.start, allocation.live.end);
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+// a `name: auto` cell waiting for a position
+struct Variable {
+    name: Token,
+    // the `Declare` and every `MoveTo` of the name, in order
+    uses: Vec<usize>,
+}
+
+// brackets that may be open at once, the compiler recurses into each one
+// and has to stay within the host's stack
+pub const NESTING_LIMIT: usize = 256;
+
+fn closing_bracket(token_type: &TokenType) -> Option<(TokenType, char, char)> {
+    match token_type {
+        TokenType::BracketOpen => Some((TokenType::BracketClose, '[', ']')),
+        TokenType::ParenthesisOpen => Some((TokenType::ParenthesisClose, '(', ')')),
+        TokenType::CurlyBracketOpen => Some((TokenType::CurlyBracketClose, '{', '}')),
+        _ => None,
+    }
+}
+
+// pairs every '[', '(' and '{' with its closing bracket,
+// returns the index of the matching bracket for each bracket token,
+// nesting past `NESTING_LIMIT` is an error
+pub fn match_brackets(tokens: &[Token]) -> Result<Vec<Option<usize>>, CompileError> {
+    let mut matching = vec![None; tokens.len()];
+    let mut open: Vec<(usize, TokenType, char, char)> = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        let (closes, expected_open) = match token.token_type {
+            TokenType::BracketClose => (']', '['),
+            TokenType::ParenthesisClose => (')', '('),
+            TokenType::CurlyBracketClose => ('}', '{'),
+            _ => {
+                if let Some((expected, opens, expected_char)) = closing_bracket(&token.token_type) {
+                    if open.len() == NESTING_LIMIT {
+                        return Err(CompileError::new(
+                            CompileErrorKind::NestingLimit,
+                            &format!("'{}' nests deeper than {} brackets", opens, NESTING_LIMIT),
+                            Span::of(token),
+                        ));
+                    }
+                    open.push((index, expected, opens, expected_char));
+                }
+                continue;
+            }
+        };
+        let (start, expected, opens, expected_char) = match open.pop() {
+            Some(start) => start,
+            None => {
+                return Err(CompileError::new(
+                    CompileErrorKind::UnmatchedBracket,
+                    &format!("unmatched '{}', no '{}' before it", closes, expected_open),
+                    Span::of(token),
+                ))
+            }
+        };
+        if token.token_type != expected {
+            return Err(CompileError::new(
+                CompileErrorKind::UnmatchedBracket,
+                &format!(
+                    "expected '{}' to close '{}' from line {} char {}, found '{}'",
+                    expected_char, opens, tokens[start].y, tokens[start].x, closes
+                ),
+                Span::of(token),
+            ));
+        }
+        matching[start] = Some(index);
+        matching[index] = Some(start);
+    }
+    if let Some((start, _, opens, expected_char)) = open.pop() {
+        return Err(CompileError::new(
+            CompileErrorKind::UnmatchedBracket,
+            &format!("unmatched '{}', no '{}' found before end of program", opens, expected_char),
+            Span::of(&tokens[start]),
+        ));
+    }
+    Ok(matching)
+}
+
+pub struct Compiler {
+    tokens: Vec<Token>,
+    index: usize,
+    program: Program,
+    // calls by name are resolved once every function is known
+    unresolved_calls: Vec<(usize, Token)>,
+    // `name: auto` cells of the scope being compiled, by name and in order
+    auto_cells: HashMap<String, usize>,
+    variables: Vec<Variable>,
+    // top level auto cells go at or past this cell, see `reserve`
+    reserved: isize,
+
+    optimize: bool,
+    // decide which runs of `+` and `-` add up to the same as their steps
+    cell_type: CellType,
+    overflow: Overflow,
+    // instructions before this index are jump targets or range boundaries
+    // and must not be folded into
+    fold_barrier: usize,
+}
+
+impl Compiler {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            index: 0,
+            program: Program::default(),
+            unresolved_calls: vec![],
+            auto_cells: HashMap::new(),
+            variables: vec![],
+            reserved: 0,
+
+            optimize: true,
+            cell_type: CellType::I32,
+            overflow: Overflow::Wrap,
+            fold_barrier: 0,
+        }
+    }
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+    // the cells the program runs on, see `folds`
+    pub fn cell_type(mut self, cell_type: CellType) -> Self {
+        self.cell_type = cell_type;
+        self
+    }
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+    // appends to `program` instead of starting an empty one,
+    // the functions it defines can be called by the new code
+    pub fn extend(mut self, program: Program) -> Self {
+        self.fold_barrier = program.instructions.len();
+        self.program = program;
+        self
+    }
+    // keeps the top level auto cells off the cells before `end`, for code
+    // running on a tape that earlier code already used
+    pub fn reserve(mut self, end: isize) -> Self {
+        self.reserved = end;
+        self
+    }
+    fn error_at(&self, token: &Token, kind: CompileErrorKind, msg: &str) -> CompileError {
+        CompileError::new(kind, msg, Span::of(token))
+    }
+    fn error(&self, kind: CompileErrorKind, msg: &str) -> CompileError {
+        self.error_at(self.current(), kind, msg)
+    }
+    fn current(&self) -> &Token {
+        // the lexer always ends the stream with EndOfFile
+        &self.tokens[self.index.min(self.tokens.len() - 1)]
+    }
+    fn current_type(&self) -> TokenType {
+        self.current().token_type.clone()
+    }
+    fn advance(&mut self) {
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+    }
+    fn expect(&mut self, expected_type: TokenType, msg: &str) -> Result<(), CompileError> {
+        if self.current_type() != expected_type {
+            return Err(self.error(CompileErrorKind::UnexpectedToken, msg));
+        }
+        self.advance();
+        Ok(())
+    }
+    fn emit_at(&mut self, instruction: Instruction, token: &Token) -> usize {
+        self.program.instructions.push(instruction);
+        self.program.spans.push(Span::of(token));
+        self.program.instructions.len() - 1
+    }
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let token = self.current().clone();
+        self.emit_at(instruction, &token)
+    }
+    fn here(&mut self) -> usize {
+        self.fold_barrier = self.program.instructions.len();
+        self.fold_barrier
+    }
+    fn emit_folded(&mut self, instruction: Instruction) {
+        let token = self.current().clone();
+        self.emit_folded_at(instruction, &token);
+    }
+    // whether adding `lhs` and then `rhs` ends on the same value as adding
+    // their sum, floats round every step, and cells that saturate or fail
+    // can hit a bound halfway through a run that changes direction
+    fn folds(&self, lhs: Int, rhs: Int) -> bool {
+        match self.cell_type {
+            CellType::F64 => false,
+            CellType::Big => true,
+            _ => self.overflow == Overflow::Wrap || (lhs < 0) == (rhs < 0),
+        }
+    }
+    // `instruction` is a single `+`, `-`, `>` or `<`, or a number after `+` or `-`
+    fn emit_folded_at(&mut self, instruction: Instruction, token: &Token) {
+        let last = self.program.instructions.len();
+        if self.optimize && last > self.fold_barrier {
+            // runs too large to add up stay separate instructions
+            let folded = match (&self.program.instructions[last - 1], &instruction) {
+                (Instruction::Add(lhs), Instruction::Add(rhs)) if self.folds(*lhs, *rhs) => {
+                    lhs.checked_add(*rhs).map(Instruction::Add)
+                }
+                (Instruction::Move { offset, low, high }, Instruction::Move { offset: step, .. }) => {
+                    offset.checked_add(*step).map(|offset| Instruction::Move {
+                        offset,
+                        low: offset.min(*low),
+                        high: offset.max(*high),
+                    })
+                }
+                _ => None,
+            };
+            if let Some(folded) = folded {
+                // `+-` cancels out, `<>` still has to reach the cell before
+                if folded == Instruction::Add(0) {
+                    self.program.instructions.pop();
+                    self.program.spans.pop();
+                } else {
+                    self.program.instructions[last - 1] = folded;
+                }
+                return;
+            }
+        }
+        self.emit_at(instruction, token);
+    }
+
+    pub fn compile_program(mut self) -> Result<Program, CompileError> {
+        match_brackets(&self.tokens)?;
+        self.block(&[TokenType::EndOfFile], "")?;
+        self.allocate(None)?;
+        for (call, token) in std::mem::take(&mut self.unresolved_calls) {
+            let body = match self.program.named_functions.get(&token.value) {
+                Some(body) => body.clone(),
+                None => {
+                    return Err(self.error_at(
+                        &token,
+                        CompileErrorKind::UndefinedFunction,
+                        &format!("call to undefined function '{}'", token.value),
+                    ))
+                }
+            };
+            let limit = self.program.argument_limits.get(&body.start).copied();
+            if let Instruction::Call { callee, arguments, .. } = &mut self.program.instructions[call] {
+                if let Some(limit) = limit.filter(|limit| arguments.len() > *limit) {
+                    return Err(CompileError::new(
+                        CompileErrorKind::TooManyArguments,
+                        &format!(
+                            "'{}' takes at most {} arguments, its auto cells start at cell {}, name its argument cells",
+                            token.value, limit, limit
+                        ),
+                        self.program.spans[call],
+                    ));
+                }
+                *callee = Callee::Function(body);
+            }
+        }
+        Ok(self.program)
+    }
+
+    // compiles instructions until one of `terminators`, which is left unconsumed
+    fn block(&mut self, terminators: &[TokenType], msg: &str) -> Result<(), CompileError> {
+        while !terminators.contains(&self.current().token_type) {
+            if self.current_type() == TokenType::EndOfFile {
+                return Err(self.error(CompileErrorKind::UnexpectedEnd, msg));
+            }
+            self.single()?;
+        }
+        Ok(())
+    }
+    fn single(&mut self) -> Result<(), CompileError> {
+        match self.current_type() {
+            TokenType::MovLeftOperation => self.emit_folded(Instruction::Move { offset: -1, low: -1, high: 0 }),
+            TokenType::MovRightOperation => self.emit_folded(Instruction::Move { offset: 1, low: 0, high: 1 }),
+            TokenType::AddOperation => return self.arithmetic(Operator::Add),
+            TokenType::SubOperation => return self.arithmetic(Operator::Sub),
+            TokenType::MulOperation => return self.arithmetic(Operator::Mul),
+            TokenType::DivOperation => return self.arithmetic(Operator::Div),
+            TokenType::ModOperation => return self.arithmetic(Operator::Mod),
+            TokenType::SetOperation => return self.arithmetic(Operator::Set),
+            TokenType::PrintOut => { self.emit(Instruction::PrintOut); }
+            TokenType::PrintChar => { self.emit(Instruction::PrintChar); }
+            TokenType::PrintText => return self.print_text(),
+            TokenType::Dump => { self.emit(Instruction::Dump); }
+            TokenType::String => { self.emit(Instruction::StoreText(self.current().value.clone())); }
+            TokenType::ReadIn => return self.input(),
+            TokenType::Identifier => return self.named_cell(),
+            TokenType::BracketOpen => return self.start_loop(),
+            TokenType::ParenthesisOpen => return self.condition(),
+            TokenType::CurlyBracketOpen => return self.function(),
+            TokenType::FunctionCall => return self.call(),
+            TokenType::BracketClose => {
+                return Err(self.error(CompileErrorKind::UnexpectedToken, "unexpected end loop, start not found"))
+            }
+            TokenType::ParenthesisClose => {
+                return Err(self.error(CompileErrorKind::UnexpectedToken, "unexpected close of condition"))
+            }
+            TokenType::CurlyBracketClose => {
+                return Err(self.error(CompileErrorKind::UnexpectedToken, "unexpected end of function"))
+            }
+            TokenType::Else => {
+                return Err(self.error(CompileErrorKind::UnexpectedToken, "'else' outside of a condition body"))
+            }
+            TokenType::If => {
+                return Err(self.error(CompileErrorKind::UnexpectedToken, "'if' can only follow 'else'"))
+            }
+            _ => {
+                return Err(self.error(
+                    CompileErrorKind::UnexpectedToken,
+                    &format!("unknown instruction '{:?}' ", self.current()),
+                ))
+            }
+        }
+        self.advance();
+        Ok(())
+    }
+    fn arithmetic(&mut self, operator: Operator) -> Result<(), CompileError> {
+        // +  -  +5  *5  /@>2  =10  *0.5
+        let token = self.current().clone();
+        self.advance();
+        // `=-5`, after `+` and `-` it stays a step so `+-` still cancels out
+        let next = self.tokens.get(self.index + 1).map(|token| &token.token_type);
+        let negative = !matches!(operator, Operator::Add | Operator::Sub)
+            && self.current_type() == TokenType::SubOperation
+            && matches!(next, Some(TokenType::Integer | TokenType::FloatingPoint));
+        if negative {
+            self.advance();
+        }
+        let sign = if negative { -1 } else { 1 };
+        let operand = match self.current_type() {
+            TokenType::Integer => Some(Operand::Value(sign * self.integer("expected a number")?)),
+            TokenType::FloatingPoint => Some(Operand::Float(sign as f64 * self.float()?)),
+            TokenType::CellReference => Some(self.cell_reference()?),
+            _ => None,
+        };
+        let instruction = match (operator, operand) {
+            (Operator::Add, None) => Instruction::Add(1),
+            (Operator::Sub, None) => Instruction::Add(-1),
+            (Operator::Add, Some(Operand::Value(value))) => Instruction::Add(value),
+            (Operator::Sub, Some(Operand::Value(value))) => Instruction::Add(-value),
+            (_, None) => {
+                return Err(self.error(
+                    CompileErrorKind::UnexpectedToken,
+                    &format!("expected a number or a cell such as '@>1' after '{}'", operator.symbol()),
+                ))
+            }
+            (operator, Some(operand)) => Instruction::Arithmetic { operator, operand },
+        };
+        self.emit_folded_at(instruction, &token);
+        Ok(())
+    }
+    fn cell_reference(&mut self) -> Result<Operand, CompileError> {
+        // @>  @<  @>2  @<3
+        self.advance();
+        let direction = match self.current_type() {
+            TokenType::MovRightOperation => 1,
+            TokenType::MovLeftOperation => -1,
+            _ => return Err(self.error(CompileErrorKind::UnexpectedToken, "expected '>' or '<' after '@'")),
+        };
+        self.advance();
+        let distance = match self.current_type() {
+            TokenType::Integer => self.integer("expected a number")?,
+            _ => 1,
+        };
+        Ok(Operand::Cell(direction * distance as isize))
+    }
+    fn named_cell(&mut self) -> Result<(), CompileError> {
+        // counter: 2  or  counter: auto  or  counter
+        let name = self.current().clone();
+        self.advance();
+        if self.current_type() != TokenType::Colon {
+            if let Some(variable) = self.auto_cells.get(&name.value).copied() {
+                let index = self.emit_at(Instruction::MoveTo(0), &name);
+                self.variables[variable].uses.push(index);
+                return Ok(());
+            }
+            let position = match self.program.named_cells.get(&name.value) {
+                Some(position) => *position,
+                None => {
+                    return Err(self.error_at(
+                        &name,
+                        CompileErrorKind::UndefinedCell,
+                        &format!("unknown cell '{}', declare it with '{}: <cell>'", name.value, name.value),
+                    ))
+                }
+            };
+            self.emit_at(Instruction::MoveTo(position), &name);
+            return Ok(());
+        }
+        self.advance();
+        if self.program.named_cells.contains_key(&name.value) || self.auto_cells.contains_key(&name.value) {
+            return Err(self.error_at(
+                &name,
+                CompileErrorKind::DuplicateCell,
+                &format!("cell '{}' is already declared", name.value),
+            ));
+        }
+        if self.current_type() == TokenType::Identifier && self.current().value == "auto" {
+            self.advance();
+            let index = self.emit_at(Instruction::Declare(0), &name);
+            self.auto_cells.insert(name.value.clone(), self.variables.len());
+            self.variables.push(Variable { name, uses: vec![index] });
+            return Ok(());
+        }
+        let position = self.integer("expected a cell number or 'auto' after ':'")? as isize;
+        self.program.named_cells.insert(name.value, position);
+        Ok(())
+    }
+    // the cell after the highest named cell of the scope, None when there is none
+    fn first_free_cell(&self) -> Option<isize> {
+        match self.program.named_cells.values().max() {
+            Some(highest) => highest.checked_add(1).map(|next| next.max(0)),
+            None => Some(0),
+        }
+    }
+    // gives the `name: auto` cells of the scope that just ended a position
+    // past its highest named cell, names that are never live at the same
+    // time share a cell
+    fn allocate(&mut self, function: Option<&str>) -> Result<(), CompileError> {
+        let variables = std::mem::take(&mut self.variables);
+        self.auto_cells.clear();
+        let instructions = &self.program.instructions;
+        let loops: Vec<Range<usize>> = (0..instructions.len())
+            .filter_map(|start| match instructions[start] {
+                Instruction::LoopStart { end, .. } => Some(start..end + 1),
+                _ => None,
+            })
+            .collect();
+
+        let mut lives = vec![];
+        for variable in &variables {
+            let first = variable.uses[0];
+            let last = variable.uses[variable.uses.len() - 1];
+            let mut live = first..last + 1;
+            // a name used inside a loop it was declared outside of
+            // keeps its value for every iteration
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for repeated in &loops {
+                    let overlaps = repeated.start < live.end && live.start < repeated.end;
+                    let inside = repeated.start <= live.start && live.end <= repeated.end;
+                    let extended = live.start.min(repeated.start)..live.end.max(repeated.end);
+                    if overlaps && !inside && extended != live {
+                        live = extended;
+                        changed = true;
+                    }
+                }
+            }
+            lives.push(live);
+        }
+
+        // linear scan, the lowest free cell goes to the next name to become live
+        let mut next = self.first_free_cell();
+        if function.is_none() {
+            next = next.map(|next| next.max(self.reserved));
+        }
+        let mut order: Vec<usize> = (0..variables.len()).collect();
+        order.sort_by_key(|variable| lives[*variable].start);
+        let mut active: Vec<(usize, isize)> = vec![];
+        let mut free: Vec<isize> = vec![];
+        for variable in order {
+            let live = lives[variable].clone();
+            active.retain(|(end, position)| {
+                if *end <= live.start {
+                    free.push(*position);
+                }
+                *end > live.start
+            });
+            free.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+            let Variable { name, uses } = &variables[variable];
+            let position = match free.pop().or(next) {
+                Some(position) => position,
+                None => {
+                    return Err(self.error_at(
+                        name,
+                        CompileErrorKind::NoFreeCell,
+                        &format!("no cell left for '{}' after the highest named cell", name.value),
+                    ))
+                }
+            };
+            if Some(position) == next {
+                next = position.checked_add(1);
+            }
+            active.push((live.end, position));
+
+            for &index in uses {
+                self.program.instructions[index] = match self.program.instructions[index] {
+                    Instruction::Declare(_) => Instruction::Declare(position),
+                    _ => Instruction::MoveTo(position),
+                };
+            }
+            if function.is_none() {
+                // later code compiled with `extend` sees it as a fixed cell
+                self.program.named_cells.insert(name.value.clone(), position);
+            }
+            self.program.allocations.push(Allocation {
+                name: name.value.clone(),
+                function: function.map(str::to_string),
+                position,
+                live,
+                span: Span::of(name),
+            });
+        }
+        Ok(())
+    }
+    fn print_text(&mut self) -> Result<(), CompileError> {
+        // $"text"  or  $
+        let token = self.current().clone();
+        self.advance();
+        if self.current_type() == TokenType::String {
+            let text = self.current().value.clone();
+            self.advance();
+            self.emit_at(Instruction::PrintLiteral(text), &token);
+        } else {
+            self.emit_at(Instruction::PrintText, &token);
+        }
+        Ok(())
+    }
+    fn input(&mut self) -> Result<(), CompileError> {
+        // ?  ?byte  ?char  ?int
+        let token = self.current().clone();
+        self.advance();
+        let mut mode = None;
+        if self.current_type() == TokenType::Identifier {
+            mode = InputMode::from_name(&self.current().value);
+            if mode.is_some() {
+                self.advance();
+            }
+        }
+        self.emit_at(Instruction::ReadIn(mode), &token);
+        Ok(())
+    }
+    fn integer(&mut self, msg: &str) -> Result<Int, CompileError> {
+        if self.current_type() != TokenType::Integer {
+            return Err(self.error(CompileErrorKind::UnexpectedToken, msg));
+        }
+        match self.current().value.parse::<Int>() {
+            Ok(value) => {
+                self.advance();
+                Ok(value)
+            }
+            Err(_) => Err(self.error(CompileErrorKind::InvalidInteger, "integer does not fit in a cell")),
+        }
+    }
+    fn float(&mut self) -> Result<f64, CompileError> {
+        match self.current().value.parse::<f64>() {
+            Ok(value) => {
+                self.advance();
+                Ok(value)
+            }
+            Err(_) => Err(self.error(CompileErrorKind::InvalidInteger, "invalid floating point number")),
+        }
+    }
+    fn start_loop(&mut self) -> Result<(), CompileError> {
+        // [10 ... ]
+        let token = self.current().clone();
+        self.advance();
+        let until = self.integer("expected number of loop condition '[1]' while current_cell is not 0")?;
+        let start = self.emit_at(Instruction::LoopStart { until, end: 0 }, &token);
+        self.here();
+        self.block(&[TokenType::BracketClose], "unclosed loop, program ended")?;
+        let close = self.current().clone();
+        self.advance();
+
+        match self.program.instructions[start + 1..] {
+            [Instruction::Add(step)] if self.optimize && step.abs() == 1 => {
+                self.program.instructions.truncate(start);
+                self.program.spans.truncate(start);
+                self.emit_at(Instruction::Clear { until, step }, &token);
+            }
+            _ => {
+                let end = self.emit_at(Instruction::LoopEnd { until, start }, &close);
+                self.program.instructions[start] = Instruction::LoopStart { until, end };
+            }
+        }
+        self.here();
+        Ok(())
+    }
+    fn condition(&mut self) -> Result<(), CompileError> {
+        // (>> "==" << "&&" "!" > ">" <; ++ else if > "<" <; -- else +)
+        let mut conditions = vec![];
+        let mut elses = vec![];
+        let mut header = self.current().clone();
+        loop {
+            let start = self.emit_at(Instruction::ConditionEnd, &header);
+            self.advance();
+            let (test, body) = self.condition_header(start)?;
+            conditions.push((start, test, body));
+            self.block(&[TokenType::Else, TokenType::ParenthesisClose], "unclosed conditional, program ended")?;
+            if self.current_type() == TokenType::ParenthesisClose {
+                break;
+            }
+
+            elses.push(self.emit(Instruction::ConditionEnd));
+            self.advance();
+            self.here();
+            if self.current_type() != TokenType::If {
+                self.block(&[TokenType::ParenthesisClose], "unclosed conditional, program ended")?;
+                break;
+            }
+            header = self.current().clone();
+        }
+        let end = self.emit(Instruction::ConditionEnd);
+        self.advance();
+
+        for (branch, (start, test, body)) in conditions.into_iter().enumerate() {
+            let otherwise = elses.get(branch).map_or(end, |branch| branch + 1);
+            self.program.instructions[start] = Instruction::Condition { test, body, otherwise, end };
+        }
+        for branch in elses {
+            self.program.instructions[branch] = Instruction::Else { end };
+        }
+        Ok(())
+    }
+    // `lhs "op" rhs "&&" ... ;` of the condition at `start`,
+    // returns its test and where the body begins
+    fn condition_header(&mut self, start: usize) -> Result<(Test, usize), CompileError> {
+        // comparisons joined by `||` so far, and the `&&` chain being built
+        let mut any = None;
+        let mut all = self.comparison()?;
+        loop {
+            if self.current_type() == TokenType::SemiColon {
+                break;
+            }
+            let combinator = self.current().clone();
+            self.advance();
+            match &*combinator.value {
+                "&&" => all = Test::And(Box::new(all), Box::new(self.comparison()?)),
+                "||" => {
+                    let done = std::mem::replace(&mut all, self.comparison()?);
+                    any = Some(match any {
+                        Some(any) => Test::Or(Box::new(any), Box::new(done)),
+                        None => done,
+                    });
+                }
+                _ => {
+                    return Err(self.error_at(
+                        &combinator,
+                        CompileErrorKind::UnknownComparison,
+                        "expected '&&', '||' or ';' after a comparison",
+                    ))
+                }
+            }
+        }
+        self.advance();
+        let test = match any {
+            Some(any) => Test::Or(Box::new(any), Box::new(all)),
+            None => all,
+        };
+
+        let body = self.here();
+        self.check_pure(start + 1..body)?;
+        Ok((test, body))
+    }
+    // `"!" lhs "op" rhs`, leaves the `&&`, `||` or `;` after it
+    fn comparison(&mut self) -> Result<Test, CompileError> {
+        let mut negated = false;
+        while self.current_type() == TokenType::String && self.current().value == "!" {
+            negated = !negated;
+            self.advance();
+        }
+        let lhs = self.here();
+        self.block(&[TokenType::String], "unexpected end of program for lhs condition")?;
+        let lhs = lhs..self.here();
+        let comparison = match Comparison::from_name(&self.current().value) {
+            Some(comparison) => comparison,
+            None => return Err(self.error(CompileErrorKind::UnknownComparison, "unknown conditional")),
+        };
+        self.advance();
+
+        let rhs = self.here();
+        self.block(&[TokenType::String, TokenType::SemiColon], "unexpected end of program for rhs condition")?;
+        let rhs = rhs..self.here();
+
+        let test = Test::Compare { comparison, lhs, rhs };
+        Ok(if negated { Test::Not(Box::new(test)) } else { test })
+    }
+    // condition operands only compute a value, the tape is rolled back
+    // afterwards but output and input could not be
+    fn check_pure(&self, code: Range<usize>) -> Result<(), CompileError> {
+        for index in code {
+            let name = match self.program.instructions[index] {
+                Instruction::PrintOut => "'.'",
+                Instruction::PrintChar => "'''",
+                Instruction::PrintLiteral(_) | Instruction::PrintText => "'$'",
+                Instruction::ReadIn(_) => "'?'",
+                _ => continue,
+            };
+            return Err(CompileError::new(
+                CompileErrorKind::ImpureCondition,
+                &format!("{} is not allowed in a condition, conditions cannot print or read input", name),
+                self.program.spans[index],
+            ));
+        }
+        Ok(())
+    }
+    fn function(&mut self) -> Result<(), CompileError> {
+        // {1: ...} or {name: ...}
+        let start = self.emit(Instruction::FunctionEnd);
+        self.advance();
+        let name = self.current().clone();
+        let number = match name.token_type {
+            TokenType::Integer => Some(self.integer("invalid function number")?),
+            TokenType::Identifier => {
+                self.advance();
+                None
+            }
+            _ => {
+                return Err(self.error(
+                    CompileErrorKind::UnexpectedToken,
+                    "expected function number or name after '{'",
+                ))
+            }
+        };
+        self.expect(TokenType::Colon, "expected ':' after function name")?;
+
+        // the body runs on its own tape, cells named outside of it mean nothing there
+        let outer = (
+            std::mem::take(&mut self.program.named_cells),
+            std::mem::take(&mut self.auto_cells),
+            std::mem::take(&mut self.variables),
+        );
+        // the arguments go in the first cells, auto cells would overwrite them
+        // unless they start past every named cell, so the arguments must be named
+        let body = self.block(&[TokenType::CurlyBracketClose], "unclosed function, program ended");
+        let body = body.and_then(|_| {
+            let mut limit = None;
+            if !self.variables.is_empty() {
+                limit = self.first_free_cell().map(|cell| cell as usize);
+            }
+            self.allocate(Some(&name.value))?;
+            Ok(limit)
+        });
+        (self.program.named_cells, self.auto_cells, self.variables) = outer;
+        let limit = body?;
+        let end = self.emit(Instruction::FunctionEnd);
+        self.advance();
+        self.program.instructions[start] = Instruction::Function { end };
+
+        let body = start + 1..end;
+        if let Some(limit) = limit {
+            self.program.argument_limits.insert(body.start, limit);
+        }
+        let duplicate = match number {
+            Some(number) => self.program.numbered_functions.insert(number, body).is_some(),
+            None => self.program.named_functions.insert(name.value.clone(), body).is_some(),
+        };
+        if duplicate {
+            return Err(self.error_at(
+                &name,
+                CompileErrorKind::DuplicateFunction,
+                &format!("function '{}' is already defined", name.value),
+            ));
+        }
+        Ok(())
+    }
+    fn call(&mut self) -> Result<(), CompileError> {
+        // &(>>, >) >>;  or  &name(>>, >) >>;
+        let call = self.emit(Instruction::FunctionEnd);
+        self.advance();
+        if self.current_type() == TokenType::Identifier {
+            self.unresolved_calls.push((call, self.current().clone()));
+            self.advance();
+        }
+        self.expect(TokenType::ParenthesisOpen, "expected '(' after function call")?;
+
+        let mut arguments = vec![];
+        if self.current_type() == TokenType::ParenthesisClose {
+            self.advance();
+        } else {
+            loop {
+                let start = self.here();
+                self.block(
+                    &[TokenType::SeparatorComma, TokenType::ParenthesisClose],
+                    "unclosed function call arguments, program ended",
+                )?;
+                arguments.push(start..self.here());
+                let closed = self.current_type() == TokenType::ParenthesisClose;
+                self.advance();
+                if closed {
+                    break;
+                }
+            }
+        }
+
+        let start = self.here();
+        self.block(&[TokenType::SemiColon], "expected ';' after function call target, program ended")?;
+        let target = start..self.here();
+        self.advance();
+
+        self.program.instructions[call] = Instruction::Call { callee: Callee::Cell, arguments, target };
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use expanded_bf::compiler::Instruction;
+use expanded_bf::error::{snippet, RuntimeError, RuntimeErrorKind};
+use expanded_bf::{Cell, Hook, Vm};
+
+const HELP: &str = "commands, an empty line repeats the last one
+
+    s, step                   run one instruction
+    n, next                   run one instruction, whole loops, conditions and calls at once
+    c, continue               run until a breakpoint or a watched cell changes
+    b, break [line[:char]]    stop at a position, lists breakpoints without one
+    d, delete <number>        remove a breakpoint
+    w, watch <cell>           stop when a top level cell changes, lists watches without one
+    u, unwatch <cell>         stop watching a cell
+    p, print <cell>           print a cell
+    t, tape [radius]          print the cells around the pointer
+    loops                     print the running loops, the pointer and the depth
+    l, where                  print the current position
+    q, quit                   stop the program";
+
+enum Mode {
+    Step,
+    Continue,
+    // stops at the first instruction outside of `from..to` at `depth` or above
+    StepOver { depth: usize, from: usize, to: usize },
+}
+
+pub struct Debugger {
+    lines: Vec<String>,
+    file_name: String,
+    mode: Mode,
+    breakpoints: Vec<(u32, Option<u32>)>,
+    // cell of the top level tape -> value when last checked, functions run on their own tapes
+    watches: Vec<(isize, Cell)>,
+    last_line: u32,
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new(source: &str, file_name: &str) -> Self {
+        Self {
+            lines: source.lines().map(str::to_string).collect(),
+            file_name: file_name.to_string(),
+            mode: Mode::Step,
+            breakpoints: vec![],
+            watches: vec![],
+            last_line: 0,
+            last_command: String::new(),
+        }
+    }
+
+    fn should_stop(&mut self, vm: &Vm) -> bool {
+        let mut stop = false;
+        for (cell, last) in self.watches.iter_mut() {
+            let value = vm.main_tape().get(*cell);
+            if value != last {
+                println!("cell {} changed {} -> {}", cell, last, value);
+                *last = value.clone();
+                stop = true;
+            }
+        }
+
+        let span = vm.current_span();
+        for (line, column) in &self.breakpoints {
+            let hit = match column {
+                Some(column) => span.line == *line && span.column == *column,
+                // a line breakpoint stops once when the line is entered
+                None => span.line == *line && self.last_line != *line,
+            };
+            if hit {
+                println!("breakpoint at line {}", line);
+                stop = true;
+            }
+        }
+        self.last_line = span.line;
+
+        match self.mode {
+            Mode::Step => true,
+            Mode::Continue => stop,
+            Mode::StepOver { depth, from, to } => {
+                let pc = vm.program_counter();
+                stop || vm.depth() < depth || (vm.depth() == depth && !(from < pc && pc < to))
+            }
+        }
+    }
+
+    fn print_position(&self, vm: &Vm) {
+        let span = vm.current_span();
+        let instruction = &vm.program().instructions[vm.program_counter()];
+        println!("{}:{}:{} {:?}", self.file_name, span.line, span.column, instruction);
+        if let Some(line) = self.lines.get(span.line.saturating_sub(1) as usize) {
+            println!("{}", snippet(line, span));
+        }
+    }
+
+    fn cell(argument: Option<&str>) -> Option<isize> {
+        match argument.map(str::parse::<isize>) {
+            Some(Ok(cell)) => Some(cell),
+            _ => {
+                println!("expected a cell position");
+                None
+            }
+        }
+    }
+
+    fn breakpoint(&mut self, argument: Option<&str>) {
+        let argument = match argument {
+            Some(argument) => argument,
+            None => {
+                for (number, (line, column)) in self.breakpoints.iter().enumerate() {
+                    match column {
+                        Some(column) => println!("{}: line {} char {}", number, line, column),
+                        None => println!("{}: line {}", number, line),
+                    }
+                }
+                return;
+            }
+        };
+        let mut position = argument.splitn(2, ':').map(str::parse::<u32>);
+        match (position.next(), position.next()) {
+            (Some(Ok(line)), None) => self.breakpoints.push((line, None)),
+            (Some(Ok(line)), Some(Ok(column))) => self.breakpoints.push((line, Some(column))),
+            _ => println!("expected a position like 3 or 3:5"),
+        }
+    }
+
+    fn watch(&mut self, vm: &Vm, argument: Option<&str>) {
+        if argument.is_none() {
+            for (cell, value) in &self.watches {
+                println!("cell {} = {}", cell, value);
+            }
+            return;
+        }
+        if let Some(cell) = Self::cell(argument) {
+            self.watches.retain(|(watched, _)| *watched != cell);
+            self.watches.push((cell, vm.main_tape().get(cell).clone()));
+        }
+    }
+
+    // runs commands until one resumes the program
+    fn prompt(&mut self, vm: &Vm) -> Result<(), RuntimeError> {
+        self.print_position(vm);
+        loop {
+            crate::prompt("(debug) ");
+
+            let mut line = String::new();
+            match std::io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    return Err(RuntimeError::new(RuntimeErrorKind::Aborted, "debugger input ended", vm.current_span()))
+                }
+                Ok(_) => {}
+            }
+            if line.trim().is_empty() {
+                line = self.last_command.clone();
+            } else {
+                self.last_command = line.clone();
+            }
+
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("");
+            let argument = words.next();
+            match command {
+                "s" | "step" => {
+                    self.mode = Mode::Step;
+                    return Ok(());
+                }
+                "n" | "next" => {
+                    let pc = vm.program_counter();
+                    let to = match &vm.program().instructions[pc] {
+                        Instruction::LoopStart { end, .. }
+                        | Instruction::Condition { end, .. }
+                        | Instruction::Function { end } => end + 1,
+                        Instruction::Call { target, .. } => target.end,
+                        _ => pc + 1,
+                    };
+                    self.mode = Mode::StepOver { depth: vm.depth(), from: pc, to };
+                    return Ok(());
+                }
+                "c" | "continue" => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "q" | "quit" => {
+                    return Err(RuntimeError::new(RuntimeErrorKind::Aborted, "stopped by the debugger", vm.current_span()))
+                }
+                "b" | "break" => self.breakpoint(argument),
+                "d" | "delete" => match argument.map(str::parse::<usize>) {
+                    Some(Ok(number)) if number < self.breakpoints.len() => {
+                        self.breakpoints.remove(number);
+                    }
+                    _ => println!("expected a breakpoint number, 'break' lists them"),
+                },
+                "w" | "watch" => self.watch(vm, argument),
+                "u" | "unwatch" => {
+                    if let Some(cell) = Self::cell(argument) {
+                        self.watches.retain(|(watched, _)| *watched != cell);
+                    }
+                }
+                "p" | "print" => {
+                    if let Some(cell) = Self::cell(argument) {
+                        println!("cell {} = {}", cell, vm.tape().get(cell));
+                    }
+                }
+                "t" | "tape" => {
+                    let radius = argument.and_then(|radius| radius.parse().ok()).unwrap_or(4);
+                    println!("{}", vm.tape().window(radius));
+                }
+                "loops" => println!("{}", vm.loops_report()),
+                "l" | "where" => self.print_position(vm),
+                "h" | "help" => println!("{}", HELP),
+                _ => println!("unknown command '{}', try help", command),
+            }
+        }
+    }
+}
+
+impl Hook for Debugger {
+    fn before_instruction(&mut self, vm: &Vm<'_>) -> Result<(), RuntimeError> {
+        if self.should_stop(vm) {
+            self.prompt(vm)?;
+        }
+        Ok(())
+    }
+    fn breakpoint(&mut self, vm: &Vm<'_>) -> Result<(), RuntimeError> {
+        println!("paused by '#'");
+        self.prompt(vm)
+    }
+}
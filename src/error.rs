@@ -0,0 +1,205 @@
+use std::fmt;
+use crate::lexer::Span;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LexErrorKind {
+    UnclosedString,
+    UnknownEscape,
+    UnknownCharacter,
+    // the lexer ended up in a state it should never be in
+    Internal,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub message: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompileErrorKind {
+    UnmatchedBracket,
+    UnexpectedToken,
+    UnexpectedEnd,
+    UnknownComparison,
+    InvalidInteger,
+    UndefinedFunction,
+    DuplicateFunction,
+    UndefinedCell,
+    DuplicateCell,
+    // a `name: auto` cell would go past the last position of the tape
+    NoFreeCell,
+    // a call passes arguments into the auto cells of the function
+    TooManyArguments,
+    // output or input inside the operands of a condition
+    ImpureCondition,
+    // brackets nested past `compiler::NESTING_LIMIT`
+    NestingLimit,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CompileError {
+    pub kind: CompileErrorKind,
+    pub message: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RuntimeErrorKind {
+    OutOfBounds,
+    UndefinedFunction,
+    TooManyArguments,
+    // function calls and condition operands nested deeper than `Vm::depth_limit`
+    RecursionLimit,
+    InvalidCharacter,
+    InvalidInput,
+    DivisionByZero,
+    // output or input reached while evaluating the operands of a condition,
+    // through a function call
+    ImpureCondition,
+    // a cell went out of range with `Overflow::Error`
+    Overflow,
+    // a float literal or `?float` input with a cell type other than `CellType::F64`
+    FloatInIntegerCell,
+    Io,
+    // a hook such as a debugger stopped the program
+    Aborted,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub message: String,
+    pub span: Span,
+}
+
+impl LexError {
+    pub fn new(kind: LexErrorKind, message: &str, span: Span) -> Self {
+        Self { kind, message: message.to_string(), span }
+    }
+}
+
+impl CompileError {
+    pub fn new(kind: CompileErrorKind, message: &str, span: Span) -> Self {
+        Self { kind, message: message.to_string(), span }
+    }
+}
+
+impl RuntimeError {
+    pub fn new(kind: RuntimeErrorKind, message: &str, span: Span) -> Self {
+        Self { kind, message: message.to_string(), span }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at line {} char {} '{}'", self.span.line, self.span.column, self.message)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at line {} char {} '{}'", self.span.line, self.span.column, self.message)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at line {} char {} '{}'", self.span.line, self.span.column, self.message)
+    }
+}
+
+impl std::error::Error for LexError {}
+impl std::error::Error for CompileError {}
+impl std::error::Error for RuntimeError {}
+
+// any error that can stop a program from running
+#[derive(Debug)]
+pub enum Error {
+    Lex(LexError),
+    Compile(CompileError),
+    Runtime(RuntimeError),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(err) => write!(f, "lex error {}", err),
+            Error::Compile(err) => write!(f, "compile error {}", err),
+            Error::Runtime(err) => write!(f, "runtime error {}", err),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    // rustc style report pointing at the offending line of `source`
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let (label, message, span) = match self {
+            Error::Lex(err) => ("lex error", &err.message, err.span),
+            Error::Compile(err) => ("compile error", &err.message, err.span),
+            Error::Runtime(err) => ("runtime error", &err.message, err.span),
+            Error::Io(err) => return format!("error: {}", err),
+        };
+        render_snippet(label, message, span, source, file_name)
+    }
+}
+
+fn render_snippet(label: &str, message: &str, span: Span, source: &str, file_name: &str) -> String {
+    let mut report = format!("{}: {}\n", label, message);
+    let line = match source.lines().nth(span.line.saturating_sub(1) as usize) {
+        Some(line) if span.line > 0 => line,
+        _ => {
+            report += &format!(" --> {}", file_name);
+            return report;
+        }
+    };
+    let gutter = " ".repeat(span.line.to_string().len());
+    report += &format!("{}--> {}:{}:{}\n", gutter, file_name, span.line, span.column);
+    report += &format!("{} |\n", gutter);
+    report += &snippet(line, span);
+    report
+}
+
+// `line`, the source line of `span`, with a caret under its column
+//
+// 3 | +++ [0 -]
+//   |     ^
+pub fn snippet(line: &str, span: Span) -> String {
+    let number = span.line.to_string();
+    // keep tabs so the caret lines up with the source line
+    let padding: String = line
+        .chars()
+        .take(span.column.saturating_sub(1) as usize)
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!("{} | {}\n{} | {}^", number, line, " ".repeat(number.len()), padding)
+}
+
+impl From<LexError> for Error {
+    fn from(err: LexError) -> Self {
+        Error::Lex(err)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(err: CompileError) -> Self {
+        Error::Compile(err)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::{LexError, LexErrorKind};
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum TokenType {
     // types
@@ -8,13 +10,26 @@ pub enum TokenType {
 
     Identifier,
 
+    // keywords
+    Else,
+    If,
+
     AddOperation,
     SubOperation,
+    MulOperation,
+    DivOperation,
+    ModOperation,
+    SetOperation,
     MovRightOperation,
     MovLeftOperation,
 
     // symbols
     PrintOut,
+    PrintChar,
+    PrintText,
+    ReadIn,
+    Dump,
+    CellReference,
     Colon,
     SemiColon,
     EndOfFile,
@@ -39,6 +54,21 @@ pub struct Token {
     pub y: u32,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    pub fn of(token: &Token) -> Self {
+        Self {
+            line: token.y,
+            column: token.x,
+        }
+    }
+}
+
 impl Token {
     pub fn new(token_type: TokenType, value: String) -> Self {
         Self {
@@ -84,6 +114,12 @@ pub struct Lexer {
     current_tokens: Vec<Token>,
 }
 
+impl Default for Lexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Lexer {
     pub fn new() -> Self {
         Self {
@@ -98,11 +134,22 @@ impl Lexer {
             current_tokens: vec![],
         }
     }
-    pub fn lex_string(text: String) -> Vec<Token> {
+    pub fn lex_string(text: String) -> Result<Vec<Token>, LexError> {
         Lexer::new().lex_text(text)
     }
-    fn error(&self, msg: &str) -> !{
-        panic!("at line {} char {} '{}'", self.y, self.x, msg)
+    fn error(&self, kind: LexErrorKind, msg: &str) -> LexError {
+        let span = Span {
+            line: self.y as u32,
+            column: self.x as u32,
+        };
+        LexError::new(kind, msg, span)
+    }
+    fn token_error(&self, kind: LexErrorKind, msg: &str) -> LexError {
+        let span = Span {
+            line: self.tok_start_y as u32,
+            column: self.tok_start_x as u32,
+        };
+        LexError::new(kind, msg, span)
     }
     fn pos_starter(&mut self) {
         self.tok_start_x = self.x;
@@ -126,15 +173,15 @@ impl Lexer {
     fn get_next_char_ignore_space(&self) -> Option<char> {
         let mut ind = self.index as usize;
         ind += 1;
-        let mut character = self.text_to_lex[ind];
+        let mut character = *self.text_to_lex.get(ind)?;
         while character == ' ' {
             ind += 1;
-            character = self.text_to_lex[ind];
+            character = *self.text_to_lex.get(ind)?;
         }
         Some(character)
     }
     fn get_char(&self, ahead: i32) -> Option<char> {
-        Some(self.text_to_lex[(self.index + ahead) as usize])
+        self.text_to_lex.get((self.index + ahead) as usize).copied()
     }
     fn get_next_char(&self) -> Option<char> {
         self.get_char(1)
@@ -148,9 +195,6 @@ impl Lexer {
     fn add_special(&mut self, tok_type: TokenType) {
         self.add_base(tok_type, "".to_string());
     }
-    fn add_special_bare(&mut self, tok_type: TokenType, value: String) {
-        self.add_base(tok_type, value);
-    }
     fn add_string(&mut self, value: String) {
         self.add_base(TokenType::String, value);
     }
@@ -162,10 +206,12 @@ impl Lexer {
     }
     fn unknown_length(&mut self, value: String) {
         match &*value {
+            "else" => self.add_base(TokenType::Else, value),
+            "if" => self.add_base(TokenType::If, value),
             _ => self.add_base(TokenType::Identifier, value),
         }
     }
-    fn lex(&mut self) -> Vec<Token> {
+    fn lex(&mut self) -> Result<Vec<Token>, LexError> {
         /*
         hierarchy
 
@@ -216,17 +262,16 @@ impl Lexer {
                                 self.add_float(unknown_length.clone());
                                 float = false;
                             } else {
-                                unreachable!()
+                                return Err(self.error(LexErrorKind::Internal, "number is neither int nor float"));
                             }
                             num_on = false;
                         } else if id_on {
                             self.unknown_length(unknown_length);
                             id_on = false;
                         } else {
-                            self.error("shit something went wrong!");
+                            return Err(self.error(LexErrorKind::Internal, "shit something went wrong!"));
                         }
 
-                        unknown_length_being_used = false;
                         unknown_length = "".to_string();
                     }
 
@@ -236,7 +281,7 @@ impl Lexer {
             } else if str_on || comment_on {
                 if self.current_char == '\n' {
                     if str_on {
-                        self.error("unclosed string at line");
+                        return Err(self.token_error(LexErrorKind::UnclosedString, "unclosed string literal"));
                     } else {
                         comment_on = false;
                         unknown_length_being_used = false;
@@ -254,8 +299,19 @@ impl Lexer {
                         't' => {
                             unknown_length += "\t";
                         }
+                        '0' => {
+                            unknown_length += "\0";
+                        }
+                        '"' => {
+                            unknown_length += "\"";
+                        }
                         ' ' => {}
-                        _ => unimplemented!(),
+                        _ => {
+                            return Err(self.error(
+                                LexErrorKind::UnknownEscape,
+                                &format!("unknown escape '\\{}'", self.current_char),
+                            ))
+                        }
                     }
                 } else {
                     unknown_length += &self.current_char.to_string();
@@ -270,17 +326,14 @@ impl Lexer {
                     int = true;
 
                     if unknown_length_being_used {
-                        self.error("shit something went wrong");
+                        return Err(self.error(LexErrorKind::Internal, "shit something went wrong"));
                     }
 
                     unknown_length_being_used = true;
                 }
             } else if self.current_char == '.'
                 && num_on
-                && num.contains(&self.get_next_char_ignore_space().expect(&format!(
-                "expected char! at line {} char {}",
-                self.y, self.x
-            )))
+                && self.get_next_char_ignore_space().is_some_and(|next| num.contains(&next))
             {
                 int = false;
                 float = true;
@@ -295,7 +348,7 @@ impl Lexer {
                         self.add_float(unknown_length.clone());
                         float = false;
                     } else {
-                        self.error("what? how?!");
+                        return Err(self.error(LexErrorKind::Internal, "what? how?!"));
                     }
                     num_on = false;
                     unknown_length_being_used = false;
@@ -310,7 +363,7 @@ impl Lexer {
                     id_on = true;
 
                     if unknown_length_being_used {
-                        self.error("shit something went wrong!");
+                        return Err(self.error(LexErrorKind::Internal, "shit something went wrong!"));
                     }
 
                     unknown_length_being_used = true;
@@ -324,7 +377,7 @@ impl Lexer {
                         self.add_float(unknown_length.clone());
                         float = false;
                     } else {
-                        self.error("what? how?!");
+                        return Err(self.error(LexErrorKind::Internal, "what? how?!"));
                     }
                     num_on = false;
                     unknown_length_being_used = false;
@@ -343,6 +396,14 @@ impl Lexer {
                     '>' => self.add_special(TokenType::MovRightOperation),
                     '<' => self.add_special(TokenType::MovLeftOperation),
                     '.' => self.add_special(TokenType::PrintOut),
+                    '\'' => self.add_special(TokenType::PrintChar),
+                    '$' => self.add_special(TokenType::PrintText),
+                    '?' => self.add_special(TokenType::ReadIn),
+                    '#' => self.add_special(TokenType::Dump),
+                    '*' => self.add_special(TokenType::MulOperation),
+                    '%' => self.add_special(TokenType::ModOperation),
+                    '=' => self.add_special(TokenType::SetOperation),
+                    '@' => self.add_special(TokenType::CellReference),
                     '(' => self.add_special(TokenType::ParenthesisOpen),
                     ')' => self.add_special(TokenType::ParenthesisClose),
                     ',' => self.add_special(TokenType::SeparatorComma),
@@ -355,24 +416,18 @@ impl Lexer {
                     '&' => self.add_special(TokenType::FunctionCall),
                     '\n' | ' ' | '\t' => {}
                     '/' => {
-                        match self.get_next_char() {
-                            Some(char) => {
-                                if char != '/'{
-                                    self.error(&*format!("not  added -> / <-,", ))
-                                }
-                                comment_on = true;
-                                self.next_char();
-                            }
-                            None => self.error(&*format!("not added -> / <-,", ))
+                        if self.get_next_char() == Some('/') {
+                            comment_on = true;
+                            self.next_char();
+                        } else {
+                            self.add_special(TokenType::DivOperation);
                         }
                     }
                     _ => {
-                        unimplemented!(
-                            "not added -> {} <-, at line {} char {}",
-                            self.current_char,
-                            self.tok_start_y,
-                            self.tok_start_x
-                        )
+                        return Err(self.error(
+                            LexErrorKind::UnknownCharacter,
+                            &format!("not added -> {} <-,", self.current_char),
+                        ))
                     }
                 }
             }
@@ -383,18 +438,18 @@ impl Lexer {
             } else if float {
                 self.add_float(unknown_length.clone());
             } else {
-                self.error("what? how?!")
+                return Err(self.error(LexErrorKind::Internal, "what? how?!"));
             }
         } else if id_on {
             self.unknown_length(unknown_length)
         } else if str_on {
-            self.error("unclosed string literal");
+            return Err(self.token_error(LexErrorKind::UnclosedString, "unclosed string literal"));
         }
         self.add_special(TokenType::EndOfFile);
-        self.current_tokens.clone()
+        Ok(self.current_tokens.clone())
     }
 
-    pub fn lex_text(&mut self, text: String) -> Vec<Token> {
+    pub fn lex_text(&mut self, text: String) -> Result<Vec<Token>, LexError> {
         self.text_to_lex = text.chars().collect();
         self.lex()
     }
@@ -0,0 +1,22 @@
+pub mod cell;
+pub mod lexer;
+pub mod tape;
+pub mod compiler;
+pub mod error;
+pub mod vm;
+
+pub use crate::cell::{Cell, CellType, Overflow};
+pub use crate::error::Error;
+pub use crate::tape::Tape;
+pub use crate::vm::{Hook, InputMode, Vm};
+
+use crate::compiler::{Compiler, Program};
+use crate::lexer::Lexer;
+
+pub type Int = i64;
+
+// lexes and compiles `source` without running it
+pub fn compile(source: &str, optimize: bool) -> Result<Program, Error> {
+    let tokens = Lexer::lex_string(source.to_string())?;
+    Ok(Compiler::new(tokens).optimize(optimize).compile_program()?)
+}
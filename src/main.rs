@@ -1,220 +1,230 @@
-use crate::lexer::{Token, TokenType};
-
-mod lexer;
-
-const STACK_SIZE: usize = 1000;
-
-type Int = i32;
-
-struct VM {
-    program: Vec<Token>,
-    program_counter: usize,
-    current_instruction: Token,
+use std::io::{Read, Write};
+use std::process::exit;
+use expanded_bf::compiler::Compiler;
+use expanded_bf::lexer::Lexer;
+use expanded_bf::tape::TAPE_LIMIT;
+use expanded_bf::vm::DEPTH_LIMIT;
+use expanded_bf::{CellType, Error, InputMode, Overflow, Vm};
+
+mod debugger;
+mod repl;
+
+const USAGE: &str = "usage: expanded_bf <command> [options] <file>
+
+commands:
+    run <file>       run a program, also used when no command is given
+    tokens <file>    print the tokens of a program
+    check <file>     lex and compile a program without running it, lists its named cells
+    debug <file>     step through a program, 'help' at the prompt lists commands
+    repl             run code line by line on a persistent tape
+
+<file> can be '-' to read the program from stdin
+
+options:
+    --tape-limit <cells>   most cells the tape grows to, 'none' for no limit (default 1048576)
+    --bidirectional        let the tape grow left of cell 0
+    --depth-limit <depth>  most function calls and conditions running inside each other,
+                           at most and by default 256
+    --cell-width <type>    u8, u16, u32, i32, i64, big for unbounded cells or f64 (default i32)
+    --overflow <policy>    wrap, saturate or error when a cell leaves its range (default wrap)
+    --input-mode <mode>    byte, char, int or float, read by a bare '?' (default int)
+    --no-optimize          do not fold instructions, useful when debugging
+    -h, --help             print this message";
+
+#[derive(PartialEq, Eq, Debug)]
+enum Command {
+    Run,
+    Tokens,
+    Check,
+    Debug,
+    Repl,
+}
 
-    loops: Vec<(usize, Int)>,
-    stack_pointer: usize,
-    stack: [Int; STACK_SIZE],
-    stored: (Vec<(usize, Int)>, usize, [Int; STACK_SIZE])
+struct Options {
+    command: Command,
+    file: String,
+    tape_limit: Option<usize>,
+    bidirectional: bool,
+    depth_limit: usize,
+    cell_type: CellType,
+    overflow: Overflow,
+    optimize: bool,
+    input_mode: InputMode,
 }
 
-impl VM {
-    fn new(program: Vec<Token>) -> Self {
-        Self {
-            program,
-            program_counter: 0,
-            current_instruction: Token {
-                token_type: TokenType::NullForParser,
-                value: "".to_string(),
-                x: 0,
-                y: 0,
-            },
-
-            loops: vec![],
-            stack_pointer: 0,
-            stack: [0; STACK_SIZE],
-            stored: (vec![], 0, [0; STACK_SIZE])
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        command: Command::Run,
+        file: String::new(),
+        tape_limit: Some(TAPE_LIMIT),
+        bidirectional: false,
+        depth_limit: DEPTH_LIMIT,
+        cell_type: CellType::I32,
+        overflow: Overflow::Wrap,
+        optimize: true,
+        input_mode: InputMode::Integer,
+    };
+    let mut file = None;
+    let mut command = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for '{}'", name));
+        match &*arg {
+            "-h" | "--help" => return Ok(None),
+            "--no-optimize" => options.optimize = false,
+            "--bidirectional" => options.bidirectional = true,
+            "--tape-limit" => {
+                let cells = value(&arg)?;
+                options.tape_limit = match cells.parse::<usize>() {
+                    _ if cells == "none" => None,
+                    Ok(cells) if cells > 0 => Some(cells),
+                    _ => return Err(format!("invalid tape limit '{}'", cells)),
+                };
+            }
+            "--depth-limit" => {
+                let depth = value(&arg)?;
+                options.depth_limit = match depth.parse::<usize>() {
+                    Ok(depth) if depth <= DEPTH_LIMIT => depth,
+                    Ok(_) => return Err(format!("depth limit '{}' is above the maximum of {}", depth, DEPTH_LIMIT)),
+                    Err(_) => return Err(format!("invalid depth limit '{}'", depth)),
+                };
+            }
+            "--cell-width" => {
+                let width = value(&arg)?;
+                options.cell_type = match CellType::from_name(&width) {
+                    Some(cell_type) => cell_type,
+                    None => return Err(format!("unsupported cell width '{}'", width)),
+                };
+            }
+            "--overflow" => {
+                let policy = value(&arg)?;
+                options.overflow = match Overflow::from_name(&policy) {
+                    Some(overflow) => overflow,
+                    None => return Err(format!("unknown overflow policy '{}'", policy)),
+                };
+            }
+            "--input-mode" => {
+                let mode = value(&arg)?;
+                options.input_mode = match InputMode::from_name(&mode) {
+                    Some(mode) => mode,
+                    None => return Err(format!("unknown input mode '{}'", mode)),
+                };
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            "run" | "tokens" | "check" | "debug" | "repl" if command.is_none() && file.is_none() => {
+                command = Some(match &*arg {
+                    "tokens" => Command::Tokens,
+                    "check" => Command::Check,
+                    "debug" => Command::Debug,
+                    "repl" => Command::Repl,
+                    _ => Command::Run,
+                });
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
         }
     }
 
-    fn error(&self, msg: &str) -> ! {
-        panic!("{}", format!("at index {} '{}'", self.program_counter, msg))
-    }
-    fn store_state(&mut self){
-        self.stored = (self.loops.clone(), self.stack_pointer, self.stack.clone())
-    }
-    fn load_state(&mut self){
-        self.loops = self.stored.0.clone();
-        self.stack_pointer = self.stored.1;
-        self.stack = self.stored.2.clone();
-    }
+    options.command = command.unwrap_or(Command::Run);
+    options.file = match (&options.command, file) {
+        (Command::Repl, None) => String::new(),
+        (Command::Repl, Some(file)) => return Err(format!("unexpected argument '{}'", file)),
+        (_, file) => file.ok_or("missing program file")?,
+    };
+    Ok(Some(options))
+}
 
-    fn next_instruction(&mut self) -> bool {
-        self.program_counter += 1;
-        if self.program_counter >= self.program.len() {
-            false
-        } else {
-            self.current_instruction = self.program[self.program_counter].clone();
-            true
-        }
-    }
-    fn next_instruction_expect(&mut self, expected_type: TokenType, msg: &str) {
-        if !self.next_instruction() || self.current_instruction.token_type != expected_type {
-            self.error(msg)
-        }
-    }
+fn read_source(file: &str) -> Result<String, String> {
+    let mut source = String::new();
+    let result = if file == "-" {
+        std::io::stdin().read_to_string(&mut source).map(|_| ())
+    } else {
+        std::fs::read_to_string(file).map(|contents| source = contents)
+    };
+    result.map_err(|err| format!("{}: {}", file, err))?;
+    Ok(source)
+}
 
-    fn increment(&mut self) {
-        self.stack[self.stack_pointer] += 1;
-    }
+fn vm(options: &Options) -> Vm<'static> {
+    Vm::new()
+        .tape_limit(options.tape_limit)
+        .bidirectional(options.bidirectional)
+        .depth_limit(options.depth_limit)
+        .cell_type(options.cell_type)
+        .overflow(options.overflow)
+        .optimize(options.optimize)
+        .input_mode(options.input_mode)
+}
 
-    fn decrement(&mut self) {
-        self.stack[self.stack_pointer] -= 1;
-    }
+// prints `prompt` without a newline for the repl and the debugger
+fn prompt(prompt: &str) {
+    print!("{}", prompt);
+    // the prompt is best effort, reading still works without it
+    let _ = std::io::stdout().flush();
+}
 
-    fn sp_left(&mut self) {
-        if self.stack_pointer < 1 {
-            self.error("invalid move, going to negatives")
-        }
-        self.stack_pointer -= 1;
-    }
-    fn sp_right(&mut self) {
-        self.stack_pointer += 1;
-        if self.stack_pointer >= self.stack.len() {
-            self.error("move causes out of bounds, increase stack size")
-        }
-    }
+fn file_name(options: &Options) -> &str {
+    if options.file == "-" { "<stdin>" } else { &options.file }
+}
 
-    fn print_current(&self) {
-        println!("{}", self.stack[self.stack_pointer]);
-    }
-    fn start_loop_handler(&mut self) {
-        // println!(">> {} >> {:?}", self.program_counter, self.program[self.program_counter]);
-        self.next_instruction();
-        if self.current_instruction.token_type != TokenType::Integer {
-            self.error("expected number of loop condition '[1]' while current_cell is not 0")
+fn execute(options: &Options, source: &str) -> Result<(), Error> {
+    match options.command {
+        Command::Run => {
+            vm(options).run(source)?;
         }
-        let cond_value = self.current_instruction.value.parse::<Int>().unwrap();
-        if self.stack[self.stack_pointer] == cond_value {
-            while self.current_instruction.token_type != TokenType::BracketClose {
-                self.next_instruction();
+        Command::Tokens => {
+            let mut stdout = std::io::stdout().lock();
+            for token in Lexer::lex_string(source.to_string())? {
+                writeln!(stdout, "{}:{} {:?} {}", token.y, token.x, token.token_type, token.true_value())?;
             }
-        } else {
-            self.loops.push((self.program_counter, cond_value));
-        }
-    }
-    fn end_loop_handler(&mut self) {
-        if self.loops.len() == 0 {
-            self.error("unexpected end loop, start not found");
-        }
-        let (loc, cond) = self.loops.pop().unwrap();
-        if self.stack[self.stack_pointer] != cond {
-            self.program_counter = loc;
-            self.loops.push((loc, cond));
         }
-    }
-    fn get_value_from_pointed(&self) -> Int {
-        self.stack[self.stack_pointer]
-    }
-    fn condition_handler(&mut self){
-        // (>>, "==", <<; ++)
-        self.next_instruction();
-
-        self.store_state();
-        while self.current_instruction.token_type != TokenType::String {
-            if !self.single(){
-                self.error("unexpected end of program for lhs condition")
+        Command::Check => {
+            let program = Compiler::new(Lexer::lex_string(source.to_string())?)
+                .optimize(options.optimize)
+                .cell_type(options.cell_type)
+                .overflow(options.overflow)
+                .compile_program()?;
+            println!("{}: ok, {} instructions", options.file, program.instructions.len());
+            for line in program.layout() {
+                println!("    {}", line);
             }
-            self.next_instruction();
         }
-        let lhs = self.stack[self.stack_pointer];
-
-        let condition = self.current_instruction.value.clone();
-        self.next_instruction();
-
-        self.load_state();
-        self.current_instruction = self.program[self.program_counter].clone();
-        while self.current_instruction.token_type != TokenType::SemiColon {
-            if !self.single(){
-                self.error("unexpected end of program for rhs condition")
-            }
-            self.next_instruction();
-        }
-        self.next_instruction();
-
-        let rhs = self.stack[self.stack_pointer];
-        self.load_state();
-
-        let mut accepted = false;
-        match &*condition {
-            "==" => accepted = lhs == rhs,
-            ">=" => accepted = lhs >= rhs,
-            "<=" => accepted = lhs <= rhs,
-            "!=" => accepted = lhs != rhs,
-            ">" => accepted = lhs > rhs,
-            "<" => accepted = lhs < rhs,
-             _ => self.error("unknown conditional")
-        }
-
-        if accepted {
-            while self.current_instruction.token_type != TokenType::ParenthesisClose {
-                if !self.single(){
-                    self.error("unclosed conditional, true, program ended")
-                }
-                self.next_instruction();
-            }
-        } else {
-            while self.current_instruction.token_type != TokenType::ParenthesisClose {
-                self.next_instruction();
-                if self.current_instruction.token_type == TokenType::EndOfFile {
-                    self.error("unclosed conditional, false, program ended")
-                }
-            }
-        }
-    }
-    fn call_handler(&mut self){
-
-    }
-    fn single(&mut self) -> bool {
-        match self.current_instruction.token_type {
-            TokenType::MovLeftOperation => self.sp_left(),
-            TokenType::MovRightOperation => self.sp_right(),
-            TokenType::AddOperation => self.increment(),
-            TokenType::SubOperation => self.decrement(),
-            TokenType::PrintOut => self.print_current(),
-            TokenType::EndOfFile => return false,
-            TokenType::BracketOpen => self.start_loop_handler(),
-            TokenType::BracketClose => self.end_loop_handler(),
-            TokenType::ParenthesisOpen => self.condition_handler(),
-            TokenType::ParenthesisClose => self.error("unexpected close of condition"),
-            TokenType::FunctionCall => self.call_handler(),
-            _ => self.error(&*format!("unknown instruction '{:?}' ", self.current_instruction)),
-        }
-        true
-    }
-    fn run(&mut self) {
-        self.current_instruction = self.program[self.program_counter].clone();
-        loop {
-            if !self.single() { break; }
-            self.next_instruction();
-        }
-    }
-
-
-    fn run_from_str(program: &str) {
-        let program = lexer::Lexer::lex_string(program.to_string());
-        VM::new(program).run();
-    }
-    fn run_from_file(program_file: &str){
-        match std::fs::read_to_string(program_file) {
-            Ok(file_contents) => {
-                Self::run_from_str(&*file_contents)
-            }
-            Err(err) => panic!("{}", err)
+        Command::Debug => {
+            // unoptimized, so every step and breakpoint maps to one token
+            let debugger = debugger::Debugger::new(source, file_name(options));
+            let tape = vm(options).optimize(false).hook(debugger).run(source)?;
+            println!("program finished");
+            println!("{}", tape.window(4));
         }
+        Command::Repl => repl::repl(vm(options)),
     }
+    Ok(())
 }
 
-
 fn main() {
-    VM::run_from_file("main.bf");
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            exit(2);
+        }
+    };
+    let source = match options.command {
+        Command::Repl => String::new(),
+        _ => match read_source(&options.file) {
+            Ok(source) => source,
+            Err(msg) => {
+                eprintln!("error: {}", msg);
+                exit(1);
+            }
+        },
+    };
+    if let Err(err) = execute(&options, &source) {
+        eprintln!("{}", err.render(&source, file_name(&options)));
+        exit(1);
+    }
 }
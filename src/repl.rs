@@ -0,0 +1,96 @@
+use expanded_bf::lexer::{Lexer, TokenType};
+use expanded_bf::Vm;
+
+const HELP: &str = "enter code to run it on the tape, the tape is kept between lines
+
+    :tape           print every cell the pointer has reached
+    :reset          clear the tape and forget every function
+    :load <file>    run a file on the current tape
+    :help           print this message
+    :quit           leave, so does ctrl-d";
+
+// cells shown on each side of the pointer after every line
+const WINDOW: usize = 4;
+
+// brackets still open in `source`, more lines are read until it is 0
+fn open_brackets(source: &str) -> i32 {
+    let tokens = match Lexer::lex_string(source.to_string()) {
+        Ok(tokens) => tokens,
+        // let eval report it
+        Err(_) => return 0,
+    };
+    tokens.iter().fold(0, |depth, token| match token.token_type {
+        TokenType::BracketOpen | TokenType::ParenthesisOpen | TokenType::CurlyBracketOpen => depth + 1,
+        TokenType::BracketClose | TokenType::ParenthesisClose | TokenType::CurlyBracketClose => depth - 1,
+        _ => depth,
+    })
+}
+
+fn eval(vm: &mut Vm, source: &str, file_name: &str) {
+    if let Err(err) = vm.eval(source) {
+        eprintln!("{}", err.render(source, file_name));
+    }
+}
+
+fn print_tape(vm: &Vm) {
+    let tape = vm.tape();
+    println!("{}", tape.render(tape.positions()));
+}
+
+// returns false when the repl should stop
+fn command(vm: &mut Vm, line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    match (words.next().unwrap_or(""), words.next()) {
+        (":quit" | ":q", None) => return false,
+        (":help" | ":h", None) => println!("{}", HELP),
+        (":tape", None) => print_tape(vm),
+        (":reset", None) => {
+            vm.reset();
+            println!("tape cleared");
+        }
+        (":load", Some(file)) => match std::fs::read_to_string(file) {
+            Ok(source) => {
+                eval(vm, &source, file);
+                println!("{}", vm.tape().window(WINDOW));
+            }
+            Err(err) => eprintln!("error: {}: {}", file, err),
+        },
+        _ => eprintln!("unknown command '{}', try :help", line),
+    }
+    true
+}
+
+pub fn repl(mut vm: Vm) {
+    println!("expanded brainfuck repl, :help for commands");
+    let mut entry = String::new();
+    loop {
+        crate::prompt(if entry.is_empty() { "bf> " } else { "... " });
+
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("error: {}", err);
+                break;
+            }
+        }
+
+        if entry.is_empty() && line.trim_start().starts_with(':') {
+            if !command(&mut vm, line.trim()) {
+                break;
+            }
+            continue;
+        }
+        entry += &line;
+        if open_brackets(&entry) > 0 {
+            continue;
+        }
+
+        eval(&mut vm, &entry, "<repl>");
+        entry.clear();
+        let tape = vm.tape();
+        println!("{}", tape.window(WINDOW));
+    }
+    println!();
+}
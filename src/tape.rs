@@ -0,0 +1,275 @@
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use crate::cell::Cell;
+
+// cells a tape can grow to unless told otherwise
+pub const TAPE_LIMIT: usize = 1 << 20;
+
+// cells the pointer never reached read as this
+static ZERO: Cell = Cell::Int(0);
+
+// what `Tape::rollback` returns the tape to
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Checkpoint {
+    journal: usize,
+    pointer: isize,
+    positions: Range<isize>,
+}
+
+// cells are created as the pointer reaches them, a bidirectional tape also
+// grows to the left of cell 0 into negative positions
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tape {
+    cells: VecDeque<Cell>,
+    // position of `cells[0]`
+    start: isize,
+    pointer: isize,
+    // most cells the tape may hold, None grows until memory runs out
+    limit: Option<usize>,
+    bidirectional: bool,
+    // old values of the cells written since the oldest open checkpoint
+    journal: Vec<(isize, Cell)>,
+    // cells already in the journal for each open checkpoint, the newest last,
+    // a cell written over and over is only saved once
+    saved: Vec<HashSet<isize>>,
+}
+
+impl Default for Tape {
+    fn default() -> Self {
+        Self::new(Some(TAPE_LIMIT), false)
+    }
+}
+
+impl Tape {
+    pub fn new(limit: Option<usize>, bidirectional: bool) -> Self {
+        Self {
+            cells: VecDeque::from([Cell::default()]),
+            start: 0,
+            pointer: 0,
+            limit: limit.map(|limit| limit.max(1)),
+            bidirectional,
+            journal: vec![],
+            saved: vec![],
+        }
+    }
+    // a tape with the same limit and direction and nothing on it
+    pub fn empty(&self) -> Self {
+        Self::new(self.limit, self.bidirectional)
+    }
+    // an empty tape like this one holding `cells` from cell 0 on, None when they do not fit
+    pub fn with_cells(&self, cells: Vec<Cell>) -> Option<Self> {
+        let mut tape = self.empty();
+        if cells.len() > self.limit.unwrap_or(usize::MAX) {
+            return None;
+        }
+        if !cells.is_empty() {
+            tape.cells = cells.into();
+        }
+        Some(tape)
+    }
+
+    pub fn pointer(&self) -> isize {
+        self.pointer
+    }
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+    pub fn bidirectional(&self) -> bool {
+        self.bidirectional
+    }
+    // old values waiting for a rollback
+    pub fn journal_len(&self) -> usize {
+        self.journal.len()
+    }
+    // positions of every cell created so far
+    pub fn positions(&self) -> Range<isize> {
+        self.start..self.start + self.cells.len() as isize
+    }
+    pub fn get(&self, position: isize) -> &Cell {
+        match self.index(position) {
+            Some(index) => &self.cells[index],
+            None => &ZERO,
+        }
+    }
+    pub fn current(&self) -> &Cell {
+        self.get(self.pointer)
+    }
+    pub fn set(&mut self, cell: Cell) {
+        let position = self.pointer;
+        self.set_at(position, cell);
+    }
+    // the pointer always stands on a created cell, so positions it visited can be set
+    pub fn set_at(&mut self, position: isize, cell: Cell) {
+        if let Some(index) = self.index(position) {
+            let old = std::mem::replace(&mut self.cells[index], cell);
+            if let Some(saved) = self.saved.last_mut() {
+                if saved.insert(position) {
+                    self.journal.push((position, old));
+                }
+            }
+        }
+    }
+    fn index(&self, position: isize) -> Option<usize> {
+        usize::try_from(position - self.start).ok().filter(|index| *index < self.cells.len())
+    }
+
+    pub fn move_pointer(&mut self, offset: isize) -> Result<(), String> {
+        match self.pointer.checked_add(offset) {
+            Some(position) => self.move_to(position),
+            None => Err("move causes out of bounds".to_string()),
+        }
+    }
+    // creates the cells between the tape and `position` on the way
+    pub fn move_to(&mut self, position: isize) -> Result<(), String> {
+        if position < 0 && !self.bidirectional {
+            return Err("invalid move, going to negatives".to_string());
+        }
+        let positions = self.positions();
+        let grow = if position < positions.start {
+            positions.start - position
+        } else {
+            (position - positions.end + 1).max(0)
+        } as usize;
+        if let Some(limit) = self.limit {
+            if self.cells.len() + grow > limit {
+                return Err(format!("move causes out of bounds, the tape is limited to {} cells", limit));
+            }
+        }
+        if self.cells.try_reserve(grow).is_err() {
+            return Err(format!("move causes out of bounds, no memory for {} more cells", grow));
+        }
+        if position < positions.start {
+            for _ in 0..grow {
+                self.cells.push_front(Cell::default());
+            }
+            self.start = position;
+        } else {
+            self.cells.resize(self.cells.len() + grow, Cell::default());
+        }
+        self.pointer = position;
+        Ok(())
+    }
+
+    // starts recording writes so they can be undone, every checkpoint
+    // has to be rolled back, the newest first
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.saved.push(HashSet::new());
+        Checkpoint {
+            journal: self.journal.len(),
+            pointer: self.pointer,
+            positions: self.positions(),
+        }
+    }
+    // undoes every write and move since `checkpoint`, only the cells
+    // written in between are touched
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        let undo = self.journal.split_off(checkpoint.journal);
+        for (position, cell) in undo.into_iter().rev() {
+            if let Some(index) = self.index(position) {
+                self.cells[index] = cell;
+            }
+        }
+        while self.start < checkpoint.positions.start {
+            self.cells.pop_front();
+            self.start += 1;
+        }
+        self.cells.truncate((checkpoint.positions.end - self.start) as usize);
+        self.pointer = checkpoint.pointer;
+        self.saved.pop();
+    }
+
+    // the cells around the pointer, see `render`
+    pub fn window(&self, radius: usize) -> String {
+        let radius = radius as isize;
+        let positions = self.positions();
+        let start = (self.pointer - radius).max(positions.start);
+        let end = (self.pointer + radius + 1).min(positions.end);
+        self.render(start..end)
+    }
+    // lays out `range` of the tape as a position row, a value row and a caret
+    // under the pointer
+    //
+    // cell   0  1   2  3
+    // value  3  5  10  0
+    //               ^
+    pub fn render(&self, range: Range<isize>) -> String {
+        let mut positions = "cell ".to_string();
+        let mut values = "value".to_string();
+        let mut caret = "     ".to_string();
+        for position in range {
+            let value = self.get(position).to_string();
+            let width = position.to_string().len().max(value.len());
+            positions += &format!("  {:>width$}", position);
+            values += &format!("  {:>width$}", value);
+            let marker = if position == self.pointer { "^" } else { "" };
+            caret += &format!("  {:>width$}", marker);
+        }
+        format!("{}\n{}\n{}", positions, values, caret.trim_end())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_saves_each_cell_once() {
+        let mut tape = Tape::default();
+        let checkpoint = tape.checkpoint();
+        for value in 0..1000 {
+            tape.set(Cell::Int(value));
+            tape.move_to(1).unwrap();
+            tape.set(Cell::Int(value));
+            tape.move_to(0).unwrap();
+        }
+        assert_eq!(tape.journal_len(), 2);
+        tape.rollback(checkpoint);
+        assert_eq!(tape.journal_len(), 0);
+        assert_eq!((tape.get(0), tape.get(1), tape.positions()), (&Cell::Int(0), &Cell::Int(0), 0..1));
+    }
+
+    #[test]
+    fn rollback_shrinks_a_tape_grown_to_the_left() {
+        let mut tape = Tape::new(None, true);
+        tape.move_to(2).unwrap();
+        tape.set(Cell::Int(5));
+        let checkpoint = tape.checkpoint();
+        tape.move_to(-3).unwrap();
+        tape.set(Cell::Int(7));
+        tape.set_at(2, Cell::Int(6));
+        tape.move_to(4).unwrap();
+        assert_eq!(tape.positions(), -3..5);
+        tape.rollback(checkpoint);
+        assert_eq!((tape.pointer(), tape.positions()), (2, 0..3));
+        assert_eq!((tape.get(-3), tape.get(2)), (&Cell::Int(0), &Cell::Int(5)));
+    }
+
+    #[test]
+    fn nested_checkpoints_roll_back_to_their_own_state() {
+        let mut tape = Tape::new(None, true);
+        tape.set(Cell::Int(1));
+        let outer = tape.checkpoint();
+        tape.set(Cell::Int(2));
+        tape.move_to(-1).unwrap();
+        let inner = tape.checkpoint();
+        // saved again, the outer checkpoint has its own copy
+        tape.set_at(0, Cell::Int(3));
+        tape.set(Cell::Int(4));
+        tape.move_to(-2).unwrap();
+        assert_eq!(tape.journal_len(), 3);
+        tape.rollback(inner);
+        assert_eq!((tape.pointer(), tape.positions()), (-1, -1..1));
+        assert_eq!((tape.get(-1), tape.get(0)), (&Cell::Int(0), &Cell::Int(2)));
+        tape.rollback(outer);
+        assert_eq!((tape.pointer(), tape.positions(), tape.journal_len()), (0, 0..1, 0));
+        assert_eq!(tape.get(0), &Cell::Int(1));
+    }
+
+    #[test]
+    fn unlimited_tape_fails_moves_it_cannot_hold() {
+        let mut tape = Tape::new(None, true);
+        assert!(tape.move_to(isize::MAX).is_err());
+        assert!(tape.move_to(isize::MIN + 1).is_err());
+        assert_eq!((tape.pointer(), tape.positions()), (0, 0..1));
+    }
+}
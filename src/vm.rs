@@ -0,0 +1,715 @@
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::ops::Range;
+use std::rc::Rc;
+use num_bigint::BigInt;
+use crate::cell::{Cell, CellType, Operator, Overflow};
+use crate::compiler::{Callee, Compiler, Instruction, Operand, Program, Test};
+use crate::error::{Error, RuntimeError, RuntimeErrorKind};
+use crate::lexer::{Lexer, Span, Token};
+use crate::tape::Tape;
+use crate::Int;
+
+// cells shown on each side of the pointer by `#`
+const DUMP_RADIUS: usize = 4;
+// function calls and condition operands that may run inside each other,
+// each one uses some of the host's stack, this many fit in the 2 MiB stack
+// of a spawned thread even in debug builds
+pub const DEPTH_LIMIT: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputMode {
+    Byte,
+    Char,
+    Integer,
+    Float,
+}
+
+impl InputMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "byte" => Some(InputMode::Byte),
+            "char" => Some(InputMode::Char),
+            "int" => Some(InputMode::Integer),
+            "float" => Some(InputMode::Float),
+            _ => None,
+        }
+    }
+}
+
+// called before every instruction once set with `Vm::hook`, used by debuggers
+pub trait Hook {
+    // returning an error stops the program with it
+    fn before_instruction(&mut self, vm: &Vm<'_>) -> Result<(), RuntimeError>;
+    // called after a `#` dumped the state
+    fn breakpoint(&mut self, _vm: &Vm<'_>) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+}
+
+pub struct Vm<'io> {
+    program: Rc<Program>,
+    program_counter: usize,
+    // program counters to return to from conditions and function calls
+    frames: Vec<usize>,
+    // condition operands being evaluated, they may not print or read
+    pure: usize,
+    depth_limit: usize,
+
+    tape: Tape,
+    // tapes of the functions waiting on a call, the top level program first
+    callers: Vec<Tape>,
+    cell_type: CellType,
+    overflow: Overflow,
+    optimize: bool,
+
+    // used by a bare '?', '?byte', '?char' and '?int' pick their own mode
+    input_mode: InputMode,
+    input: Box<dyn Read + 'io>,
+    output: BufWriter<Box<dyn Write + 'io>>,
+    hook: Option<Box<dyn Hook + 'io>>,
+}
+
+impl Default for Vm<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'io> Vm<'io> {
+    // reads from stdin and writes to stdout, use the setters below to change that
+    pub fn new() -> Self {
+        Self {
+            program: Rc::new(Program::default()),
+            program_counter: 0,
+            frames: vec![],
+            pure: 0,
+            depth_limit: DEPTH_LIMIT,
+
+            tape: Tape::default(),
+            callers: vec![],
+            cell_type: CellType::I32,
+            overflow: Overflow::Wrap,
+            optimize: true,
+
+            input_mode: InputMode::Integer,
+            // stdin is already buffered and shared with anyone else reading it
+            input: Box::new(std::io::stdin()),
+            output: BufWriter::new(Box::new(std::io::stdout())),
+            hook: None,
+        }
+    }
+    // None lets the tape grow without a limit
+    pub fn tape_limit(mut self, limit: Option<usize>) -> Self {
+        self.tape = Tape::new(limit, self.tape.bidirectional());
+        self
+    }
+    // lets the pointer move left of cell 0
+    pub fn bidirectional(mut self, bidirectional: bool) -> Self {
+        self.tape = Tape::new(self.tape.limit(), bidirectional);
+        self
+    }
+    // most function calls and condition operands running inside each other,
+    // deeper recursion is an error, limits above `DEPTH_LIMIT` are lowered to it
+    pub fn depth_limit(mut self, limit: usize) -> Self {
+        self.depth_limit = limit.min(DEPTH_LIMIT);
+        self
+    }
+    pub fn cell_type(mut self, cell_type: CellType) -> Self {
+        self.cell_type = cell_type;
+        self
+    }
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+    pub fn input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+    pub fn input(mut self, input: impl Read + 'io) -> Self {
+        self.input = Box::new(BufReader::new(input));
+        self
+    }
+    pub fn output(mut self, output: impl Write + 'io) -> Self {
+        self.output = BufWriter::new(Box::new(output));
+        self
+    }
+    pub fn hook(mut self, hook: impl Hook + 'io) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+    // index of the instruction about to run
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+    pub fn current_span(&self) -> Span {
+        self.program.spans.get(self.program_counter).copied().unwrap_or_default()
+    }
+    // how many conditions and function calls are being evaluated
+    pub fn depth(&self) -> usize {
+        self.frames.len().saturating_sub(1)
+    }
+    // indices of the `LoopStart`s of every loop currently running,
+    // outermost first, including loops of the callers
+    pub fn loop_stack(&self) -> Vec<usize> {
+        let mut loops = vec![];
+        let positions = self.frames.iter().skip(1).chain(std::iter::once(&self.program_counter));
+        for &position in positions {
+            for (index, instruction) in self.program.instructions.iter().enumerate() {
+                if let Instruction::LoopStart { end, .. } = instruction {
+                    if index < position && position <= *end {
+                        loops.push(index);
+                    }
+                }
+            }
+        }
+        loops
+    }
+    // the pointer, the depth and every running loop, one per line
+    pub fn loops_report(&self) -> String {
+        let mut report = format!("pointer {}, depth {}", self.tape.pointer(), self.depth());
+        let loops = self.loop_stack();
+        if loops.is_empty() {
+            report += "\nno loops running";
+        }
+        for start in loops {
+            let Span { line, column } = self.program.spans[start];
+            if let Instruction::LoopStart { until, .. } = self.program.instructions[start] {
+                report += &format!("\n    loop until {} at line {} char {}", until, line, column);
+            }
+        }
+        report
+    }
+
+    // the tape of the running function, see `main_tape`
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+    // the tape of the top level program, even while a function runs
+    pub fn main_tape(&self) -> &Tape {
+        self.callers.first().unwrap_or(&self.tape)
+    }
+
+    // lexes, compiles and runs `source` on a fresh tape
+    pub fn run(&mut self, source: &str) -> Result<Tape, Error> {
+        let tokens = Lexer::lex_string(source.to_string())?;
+        let program = self.compiler(tokens).compile_program()?;
+        Ok(self.run_program(program)?)
+    }
+    // runs an already compiled program on a fresh tape
+    pub fn run_program(&mut self, program: Program) -> Result<Tape, RuntimeError> {
+        self.reset();
+        self.program = Rc::new(program);
+        self.run_from(0)?;
+        Ok(self.tape.clone())
+    }
+    // runs `source` on the current tape, functions defined by earlier
+    // calls to `eval` stay callable
+    pub fn eval(&mut self, source: &str) -> Result<(), Error> {
+        let tokens = Lexer::lex_string(source.to_string())?;
+        let start = self.program.instructions.len();
+        let program = self
+            .compiler(tokens)
+            .extend(self.program.as_ref().clone())
+            .reserve(self.tape.positions().end)
+            .compile_program()?;
+        self.program = Rc::new(program);
+        Ok(self.run_from(start)?)
+    }
+    fn compiler(&self, tokens: Vec<Token>) -> Compiler {
+        Compiler::new(tokens).optimize(self.optimize).cell_type(self.cell_type).overflow(self.overflow)
+    }
+    // clears the tape and forgets every function
+    pub fn reset(&mut self) {
+        self.program = Rc::new(Program::default());
+        self.program_counter = 0;
+        self.frames.clear();
+        self.tape = self.tape.empty();
+    }
+    fn run_from(&mut self, start: usize) -> Result<(), RuntimeError> {
+        self.frames.clear();
+        self.pure = 0;
+        let result = self.execute(start..self.program.instructions.len());
+        // whatever was printed before an error is still written out
+        self.flush()?;
+        result
+    }
+
+    fn error(&self, kind: RuntimeErrorKind, msg: &str) -> RuntimeError {
+        let span = self.program.spans.get(self.program_counter).copied().unwrap_or_default();
+        RuntimeError::new(kind, msg, span)
+    }
+    fn io_error(&self, err: std::io::Error) -> RuntimeError {
+        self.error(RuntimeErrorKind::Io, &err.to_string())
+    }
+
+    fn overflow_error(&self, value: impl std::fmt::Display) -> RuntimeError {
+        self.error(
+            RuntimeErrorKind::Overflow,
+            &format!("{} does not fit in a {} cell", value, self.cell_type.name()),
+        )
+    }
+    fn add(&mut self, amount: Int) -> Result<(), RuntimeError> {
+        let cell = self.tape.current();
+        match self.cell_type.add(self.overflow, cell, amount) {
+            Some(cell) => self.tape.set(cell),
+            None => {
+                let sign = if amount < 0 { '-' } else { '+' };
+                return Err(self.overflow_error(format!("{} {} {}", cell, sign, amount.unsigned_abs())));
+            }
+        }
+        Ok(())
+    }
+    // u8 cells get the utf-8 bytes of the text, other cells its characters
+    fn store_text(&mut self, text: &str) -> Result<(), RuntimeError> {
+        let codes: Vec<u32> = match self.cell_type {
+            CellType::U8 => text.bytes().map(u32::from).collect(),
+            _ => text.chars().map(u32::from).collect(),
+        };
+        let pointer = self.tape.pointer();
+        for (offset, code) in codes.into_iter().enumerate() {
+            self.move_to(pointer + offset as isize)?;
+            self.store(BigInt::from(code))?;
+        }
+        self.move_to(pointer)
+    }
+    // floats only go in f64 cells
+    fn check_float(&self, value: f64) -> Result<(), RuntimeError> {
+        if self.cell_type == CellType::F64 {
+            return Ok(());
+        }
+        Err(self.error(
+            RuntimeErrorKind::FloatInIntegerCell,
+            &format!("{:?} does not fit in a {} cell, floats need f64 cells", value, self.cell_type.name()),
+        ))
+    }
+    fn arithmetic(&mut self, operator: Operator, operand: Operand) -> Result<(), RuntimeError> {
+        let rhs = match operand {
+            Operand::Value(value) => Cell::Int(value),
+            Operand::Float(value) => {
+                self.check_float(value)?;
+                Cell::Float(value)
+            }
+            Operand::Cell(offset) => {
+                let position = match self.tape.pointer().checked_add(offset) {
+                    Some(position) => position,
+                    None => {
+                        return Err(self.error(RuntimeErrorKind::OutOfBounds, "cell reference causes out of bounds"))
+                    }
+                };
+                if position < 0 && !self.tape.bidirectional() {
+                    return Err(self.error(
+                        RuntimeErrorKind::OutOfBounds,
+                        &format!("cell {} is left of cell 0", position),
+                    ));
+                }
+                self.tape.get(position).clone()
+            }
+        };
+        if matches!(operator, Operator::Div | Operator::Mod) && rhs.is(0) {
+            return Err(self.error(RuntimeErrorKind::DivisionByZero, "division by zero"));
+        }
+        let lhs = self.tape.current();
+        match self.cell_type.apply(self.overflow, operator, lhs, &rhs) {
+            Some(cell) => self.tape.set(cell),
+            None => return Err(self.overflow_error(format!("{} {} {}", lhs, operator.symbol(), rhs))),
+        }
+        Ok(())
+    }
+    // stores an integer read or computed at runtime in the current cell
+    fn store(&mut self, value: BigInt) -> Result<(), RuntimeError> {
+        match self.cell_type.from_big(self.overflow, value.clone()) {
+            Some(cell) => self.tape.set(cell),
+            None => return Err(self.overflow_error(value)),
+        }
+        Ok(())
+    }
+    fn clear(&mut self, until: Int, step: Int) -> Result<(), RuntimeError> {
+        let current = self.get_value_from_pointed();
+        // fractions can step past `until`, they take the slow path
+        let exact = !matches!(current, Cell::Float(_)) || current.as_int().is_some();
+        let target = Cell::Int(until);
+        let reaches = (step > 0 && current < target) || (step < 0 && current > target);
+        let wraps = self.overflow == Overflow::Wrap && self.cell_type.bounds().is_some();
+        // a cell never holds an `until` outside of its type
+        if exact && (reaches || wraps) && self.cell_type.contains(until) {
+            return self.store(BigInt::from(until));
+        }
+        // the loop overflows or never ends, same as when it is not optimized
+        while !self.get_value_from_pointed().is(until) {
+            self.add(step)?;
+        }
+        Ok(())
+    }
+
+    fn move_pointer(&mut self, offset: isize) -> Result<(), RuntimeError> {
+        self.tape.move_pointer(offset).map_err(|msg| self.error(RuntimeErrorKind::OutOfBounds, &msg))
+    }
+    // moves by `offset` after reaching `low` and `high`, so a folded run
+    // grows the tape and fails like its steps
+    fn move_through(&mut self, offset: isize, low: isize, high: isize) -> Result<(), RuntimeError> {
+        let origin = self.tape.pointer();
+        for reach in [low, high] {
+            if reach != 0 && reach != offset {
+                self.move_pointer(reach)?;
+                self.move_to(origin)?;
+            }
+        }
+        self.move_pointer(offset)
+    }
+    fn move_to(&mut self, position: isize) -> Result<(), RuntimeError> {
+        self.tape.move_to(position).map_err(|msg| self.error(RuntimeErrorKind::OutOfBounds, &msg))
+    }
+
+    // the compiler rejects output and input written in a condition,
+    // this catches the ones in functions called from it
+    fn check_pure(&self) -> Result<(), RuntimeError> {
+        if self.pure == 0 {
+            return Ok(());
+        }
+        Err(self.error(
+            RuntimeErrorKind::ImpureCondition,
+            "a function called in a condition cannot print or read input",
+        ))
+    }
+    fn print_current(&mut self) -> Result<(), RuntimeError> {
+        writeln!(self.output, "{}", self.tape.current()).map_err(|err| self.io_error(err))
+    }
+    fn character(&self, value: &Cell) -> Result<char, RuntimeError> {
+        let code = value.as_int().and_then(|value| u32::try_from(value).ok());
+        match code.and_then(char::from_u32) {
+            Some(character) => Ok(character),
+            None => Err(self.error(
+                RuntimeErrorKind::InvalidCharacter,
+                &format!("cell value {} is not a valid character", value),
+            )),
+        }
+    }
+    // u8 cells are written as raw bytes, so programs can print utf-8 a byte
+    // at a time like classic brainfuck, other cells as characters
+    fn print_char(&mut self) -> Result<(), RuntimeError> {
+        let value = self.tape.current();
+        if let Some(byte) = self.byte(value) {
+            return self.output.write_all(&[byte]).map_err(|err| self.io_error(err));
+        }
+        let character = self.character(value)?;
+        write!(self.output, "{}", character).map_err(|err| self.io_error(err))
+    }
+    fn byte(&self, value: &Cell) -> Option<u8> {
+        match self.cell_type {
+            CellType::U8 => value.as_int().and_then(|value| u8::try_from(value).ok()),
+            _ => None,
+        }
+    }
+    fn print_literal(&mut self, text: &str) -> Result<(), RuntimeError> {
+        write!(self.output, "{}", text).map_err(|err| self.io_error(err))
+    }
+    // prints the cells from the pointer on as text, up to the first zero cell,
+    // written the same way as `print_char`
+    fn print_text(&mut self) -> Result<(), RuntimeError> {
+        let mut text = vec![];
+        for position in self.tape.pointer().. {
+            let value = self.tape.get(position);
+            if value.is(0) {
+                break;
+            }
+            match self.byte(value) {
+                Some(byte) => text.push(byte),
+                None => text.extend(self.character(value)?.to_string().bytes()),
+            }
+        }
+        self.output.write_all(&text).map_err(|err| self.io_error(err))
+    }
+    fn flush(&mut self) -> Result<(), RuntimeError> {
+        self.output.flush().map_err(|err| self.io_error(err))
+    }
+    fn read_byte(&mut self) -> Result<Option<u8>, RuntimeError> {
+        let mut byte = [0];
+        loop {
+            match self.input.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(self.io_error(err)),
+            }
+        }
+    }
+    fn read_char(&mut self) -> Result<Option<char>, RuntimeError> {
+        let mut bytes = match self.read_byte()? {
+            Some(byte) => vec![byte],
+            None => return Ok(None),
+        };
+        loop {
+            match std::str::from_utf8(&bytes) {
+                Ok(text) => return Ok(text.chars().next()),
+                Err(err) if err.error_len().is_some() || bytes.len() == 4 => {
+                    return Err(self.error(RuntimeErrorKind::InvalidInput, "input is not valid utf-8"))
+                }
+                Err(_) => match self.read_byte()? {
+                    Some(byte) => bytes.push(byte),
+                    None => {
+                        return Err(self.error(
+                            RuntimeErrorKind::InvalidInput,
+                            "input ended inside a utf-8 character",
+                        ))
+                    }
+                },
+            }
+        }
+    }
+    // the next whitespace separated word
+    fn read_word(&mut self) -> Result<Option<String>, RuntimeError> {
+        let mut byte = match self.read_byte()? {
+            Some(byte) => byte,
+            None => return Ok(None),
+        };
+        while byte.is_ascii_whitespace() {
+            byte = match self.read_byte()? {
+                Some(byte) => byte,
+                None => return Ok(None),
+            };
+        }
+        let mut text = String::new();
+        while !byte.is_ascii_whitespace() {
+            text.push(byte as char);
+            match self.read_byte()? {
+                Some(next) => byte = next,
+                None => break,
+            }
+        }
+        Ok(Some(text))
+    }
+    fn read_integer(&mut self) -> Result<Option<BigInt>, RuntimeError> {
+        let text = match self.read_word()? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        match text.parse::<BigInt>() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(self.error(
+                RuntimeErrorKind::InvalidInput,
+                &format!("expected an integer as input, got '{}'", text),
+            )),
+        }
+    }
+    fn read_float(&mut self) -> Result<Option<f64>, RuntimeError> {
+        let text = match self.read_word()? {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        match text.parse::<f64>() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(self.error(
+                RuntimeErrorKind::InvalidInput,
+                &format!("expected a number as input, got '{}'", text),
+            )),
+        }
+    }
+    fn input_handler(&mut self, mode: Option<InputMode>) -> Result<(), RuntimeError> {
+        // anything printed so far should be visible before waiting on input
+        self.flush()?;
+        // end of input stores 0
+        let value = match mode.unwrap_or(self.input_mode) {
+            InputMode::Byte => self.read_byte()?.map(BigInt::from),
+            InputMode::Char => self.read_char()?.map(|character| BigInt::from(character as u32)),
+            InputMode::Integer => self.read_integer()?,
+            InputMode::Float => {
+                let value = self.read_float()?.unwrap_or_default();
+                self.check_float(value)?;
+                self.tape.set(Cell::Float(value));
+                return Ok(());
+            }
+        };
+        self.store(value.unwrap_or_default())
+    }
+    fn dump(&mut self) -> Result<(), RuntimeError> {
+        // keep the dump after whatever was printed before it
+        self.flush()?;
+        let Span { line, column } = self.current_span();
+        eprintln!("dump at line {} char {}", line, column);
+        eprintln!("{}", self.tape.window(DUMP_RADIUS));
+        eprintln!("{}", self.loops_report());
+        if let Some(mut hook) = self.hook.take() {
+            let result = hook.breakpoint(self);
+            self.hook = Some(hook);
+            result?;
+        }
+        Ok(())
+    }
+    fn get_value_from_pointed(&self) -> Cell {
+        self.tape.current().clone()
+    }
+    // runs `code` and returns the cell it ends on, leaving the pointer where it was
+    fn selected_cell(&mut self, code: Range<usize>) -> Result<isize, RuntimeError> {
+        let origin = self.tape.pointer();
+        self.execute(code)?;
+        let selected = self.tape.pointer();
+        self.move_to(origin)?;
+        Ok(selected)
+    }
+    // runs `code` and returns the value it ends on, leaving the tape as it was
+    fn evaluate(&mut self, code: Range<usize>) -> Result<Cell, RuntimeError> {
+        let checkpoint = self.tape.checkpoint();
+        self.pure += 1;
+        let result = self.execute(code);
+        self.pure -= 1;
+        let value = self.get_value_from_pointed();
+        self.tape.rollback(checkpoint);
+        result?;
+        Ok(value)
+    }
+    fn test(&mut self, test: &Test) -> Result<bool, RuntimeError> {
+        Ok(match test {
+            Test::Compare { comparison, lhs, rhs } => {
+                let lhs = self.evaluate(lhs.clone())?;
+                let rhs = self.evaluate(rhs.clone())?;
+                comparison.test(&lhs, &rhs)
+            }
+            Test::Not(test) => !self.test(test)?,
+            Test::And(lhs, rhs) => self.test(lhs)? && self.test(rhs)?,
+            Test::Or(lhs, rhs) => self.test(lhs)? || self.test(rhs)?,
+        })
+    }
+    fn call_handler(
+        &mut self,
+        callee: &Callee,
+        arguments: &[Range<usize>],
+        target: Range<usize>,
+    ) -> Result<(), RuntimeError> {
+        let body = match callee {
+            Callee::Function(body) => body.clone(),
+            Callee::Cell => {
+                let number = self.get_value_from_pointed();
+                match number.as_int().and_then(|number| self.program.numbered_functions.get(&number)) {
+                    Some(body) => body.clone(),
+                    None => {
+                        return Err(self.error(
+                            RuntimeErrorKind::UndefinedFunction,
+                            &format!("call to undefined function '{}'", number),
+                        ))
+                    }
+                }
+            }
+        };
+
+        let mut args = vec![];
+        for argument in arguments {
+            let cell = self.selected_cell(argument.clone())?;
+            args.push(self.tape.get(cell).clone());
+        }
+        let target = self.selected_cell(target)?;
+        if let Some(limit) = self.program.argument_limits.get(&body.start) {
+            if args.len() > *limit {
+                return Err(self.error(
+                    RuntimeErrorKind::TooManyArguments,
+                    &format!("function takes at most {} arguments, its auto cells start at cell {}", limit, limit),
+                ));
+            }
+        }
+
+        // the callee runs on its own tape, with the arguments in its first cells
+        let tape = match self.tape.with_cells(args) {
+            Some(tape) => tape,
+            None => {
+                return Err(self.error(RuntimeErrorKind::TooManyArguments, "too many arguments for function call"))
+            }
+        };
+        self.callers.push(std::mem::replace(&mut self.tape, tape));
+        let result = self.execute(body);
+        let caller = self.callers.pop().unwrap_or_default();
+        let callee = std::mem::replace(&mut self.tape, caller);
+        result?;
+        self.tape.set_at(target, callee.current().clone());
+        Ok(())
+    }
+    // executes one instruction and moves the program counter to the next one
+    fn single(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        let mut next = self.program_counter + 1;
+        match &program.instructions[self.program_counter] {
+            Instruction::Add(amount) => self.add(*amount)?,
+            Instruction::Move { offset, low, high } => self.move_through(*offset, *low, *high)?,
+            Instruction::MoveTo(position) => self.move_to(*position)?,
+            // cells the pointer never reached are already 0
+            Instruction::Declare(position) => self.tape.set_at(*position, Cell::default()),
+            Instruction::Arithmetic { operator, operand } => self.arithmetic(*operator, *operand)?,
+            Instruction::Clear { until, step } => self.clear(*until, *step)?,
+            Instruction::PrintOut => {
+                self.check_pure()?;
+                self.print_current()?
+            }
+            Instruction::PrintChar => {
+                self.check_pure()?;
+                self.print_char()?
+            }
+            Instruction::PrintLiteral(text) => {
+                self.check_pure()?;
+                self.print_literal(text)?
+            }
+            Instruction::PrintText => {
+                self.check_pure()?;
+                self.print_text()?
+            }
+            Instruction::ReadIn(mode) => {
+                self.check_pure()?;
+                self.input_handler(*mode)?
+            }
+            Instruction::StoreText(text) => self.store_text(text)?,
+            Instruction::Dump => self.dump()?,
+            Instruction::LoopStart { until, end } => {
+                if self.tape.current().is(*until) {
+                    next = end + 1;
+                }
+            }
+            Instruction::LoopEnd { until, start } => {
+                if !self.tape.current().is(*until) {
+                    next = start + 1;
+                }
+            }
+            Instruction::Condition { test, body, otherwise, .. } => {
+                next = if self.test(test)? { *body } else { *otherwise };
+            }
+            Instruction::Else { end } => next = end + 1,
+            Instruction::ConditionEnd => {}
+            Instruction::Function { end } => next = end + 1,
+            Instruction::FunctionEnd => {}
+            Instruction::Call { callee, arguments, target } => {
+                self.call_handler(callee, arguments, target.clone())?;
+                next = target.end;
+            }
+        }
+        self.program_counter = next;
+        Ok(())
+    }
+    // runs the instructions in `code`, the program counter is restored afterwards
+    fn execute(&mut self, code: Range<usize>) -> Result<(), RuntimeError> {
+        let program = Rc::clone(&self.program);
+        if self.frames.len() > self.depth_limit {
+            return Err(self.error(
+                RuntimeErrorKind::RecursionLimit,
+                &format!("more than {} function calls and conditions running inside each other", self.depth_limit),
+            ));
+        }
+        self.frames.push(self.program_counter);
+        self.program_counter = code.start;
+        while self.program_counter < code.end {
+            if let Some(mut hook) = self.hook.take() {
+                // output shows up while the hook has control
+                self.flush()?;
+                let result = hook.before_instruction(self);
+                self.hook = Some(hook);
+                result?;
+            }
+            self.single(&program)?;
+        }
+        self.program_counter = self.frames.pop().unwrap_or_default();
+        Ok(())
+    }
+}
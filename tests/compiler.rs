@@ -0,0 +1,126 @@
+use expanded_bf::compiler::{match_brackets, NESTING_LIMIT};
+use expanded_bf::error::{CompileError, CompileErrorKind};
+use expanded_bf::lexer::{Lexer, Span};
+use expanded_bf::{Cell, Vm};
+
+fn brackets(source: &str) -> Result<Vec<Option<usize>>, CompileError> {
+    match_brackets(&Lexer::lex_string(source.to_string()).unwrap())
+}
+
+// the message and where it points, for a program with unmatched brackets
+fn mismatch(source: &str) -> (String, Span) {
+    let err = brackets(source).unwrap_err();
+    assert_eq!(err.kind, CompileErrorKind::UnmatchedBracket, "'{}'", source);
+    (err.message, err.span)
+}
+
+#[test]
+fn matches_nested_brackets() {
+    let matching = brackets("[0 ( + ; ) {f: - } ]").unwrap();
+    let pairs: Vec<(usize, usize)> = matching
+        .iter()
+        .enumerate()
+        .filter_map(|(index, other)| other.filter(|other| *other > index).map(|other| (index, other)))
+        .collect();
+    assert_eq!(pairs, [(0, 11), (2, 5), (6, 10)]);
+}
+
+#[test]
+fn closing_bracket_without_an_opening_one() {
+    assert_eq!(
+        mismatch("+ +\n  ] ["),
+        ("unmatched ']', no '[' before it".to_string(), Span { line: 2, column: 3 })
+    );
+    assert_eq!(mismatch(")").0, "unmatched ')', no '(' before it");
+    assert_eq!(mismatch("[0 ] }").0, "unmatched '}', no '{' before it");
+}
+
+#[test]
+fn closing_bracket_of_the_wrong_kind() {
+    assert_eq!(
+        mismatch("[0 +\n ( - ]"),
+        (
+            "expected ')' to close '(' from line 2 char 2, found ']'".to_string(),
+            Span { line: 2, column: 6 }
+        )
+    );
+    assert_eq!(mismatch("{f: [0 } ]").0, "expected ']' to close '[' from line 1 char 5, found '}'");
+}
+
+#[test]
+fn opening_bracket_never_closed() {
+    // points at the innermost bracket left open
+    assert_eq!(
+        mismatch("[0 +\n  ( - ;\n"),
+        ("unmatched '(', no ')' found before end of program".to_string(), Span { line: 2, column: 3 })
+    );
+    assert_eq!(mismatch("{f: +").0, "unmatched '{', no '}' found before end of program");
+}
+
+#[test]
+fn brackets_nested_past_the_limit() {
+    let nested = |depth| format!("{}{}", "( ".repeat(depth), ") ".repeat(depth));
+    assert!(brackets(&nested(NESTING_LIMIT)).is_ok());
+    let err = brackets(&nested(NESTING_LIMIT + 1)).unwrap_err();
+    assert_eq!(err.kind, CompileErrorKind::NestingLimit);
+    assert_eq!(err.span, Span { line: 1, column: NESTING_LIMIT as u32 * 2 + 1 });
+}
+
+fn layout(source: &str) -> Vec<String> {
+    expanded_bf::compile(source, true).unwrap().layout()
+}
+
+#[test]
+fn auto_cells_share_a_cell_once_the_first_is_done() {
+    assert_eq!(
+        layout("x: auto x +3 . y: auto y + ."),
+        ["cell 0  x, auto, live over instructions 0..2", "cell 0  y, auto, live over instructions 4..6"]
+    );
+    // both live at once, and past the named cell
+    assert_eq!(
+        layout("n: 1 x: auto y: auto x + y + x ."),
+        [
+            "cell 1  n",
+            "cell 2  x, auto, live over instructions 0..7",
+            "cell 3  y, auto, live over instructions 1..5",
+        ]
+    );
+}
+
+#[test]
+fn auto_cells_used_in_a_loop_live_until_it_ends() {
+    // x still has to count down after y is declared
+    assert_eq!(
+        layout("x: auto x +3 [0 x - y: auto y + ] ."),
+        ["cell 0  x, auto, live over instructions 0..10", "cell 1  y, auto, live over instructions 6..8"]
+    );
+    // a cell declared and used inside the loop does not need to last
+    assert_eq!(
+        layout("x: auto x +3 [0 y: auto y + x - ] z: auto z ."),
+        [
+            "cell 0  x, auto, live over instructions 0..10",
+            "cell 0  z, auto, live over instructions 10..12",
+            "cell 1  y, auto, live over instructions 4..6",
+        ]
+    );
+}
+
+#[test]
+fn functions_allocate_their_own_cells() {
+    assert_eq!(
+        layout("{f: s: auto s + t: auto t + } x: auto x ."),
+        [
+            "cell 0  x, auto, live over instructions 8..10",
+            "in f: cell 0  s, auto, live over instructions 1..3",
+            "in f: cell 0  t, auto, live over instructions 4..6",
+        ]
+    );
+}
+
+#[test]
+fn repl_auto_cells_go_past_the_cells_already_reached() {
+    let mut vm = Vm::new();
+    vm.eval(">>> +7").unwrap();
+    vm.eval("a: auto a +1").unwrap();
+    assert_eq!((vm.tape().get(3), vm.tape().get(4)), (&Cell::Int(7), &Cell::Int(1)));
+}
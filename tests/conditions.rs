@@ -0,0 +1,72 @@
+use expanded_bf::error::{RuntimeError, RuntimeErrorKind};
+use expanded_bf::{Error, Hook, Vm};
+
+// everything the program printed, or the kind of runtime error that stopped it
+fn output(source: &str) -> Result<String, RuntimeErrorKind> {
+    let mut output = Vec::new();
+    let result = Vm::new().input(&b""[..]).output(&mut output).run(source);
+    match result {
+        Ok(_) => Ok(String::from_utf8(output).unwrap()),
+        Err(Error::Runtime(err)) => Err(err.kind),
+        Err(err) => panic!("'{}' does not run: {}", source, err),
+    }
+}
+
+// the longest the undo journal got while the program ran
+struct Journal<'a>(&'a mut usize);
+
+impl Hook for Journal<'_> {
+    fn before_instruction(&mut self, vm: &Vm<'_>) -> Result<(), RuntimeError> {
+        *self.0 = (*self.0).max(vm.tape().journal_len());
+        Ok(())
+    }
+}
+
+#[test]
+fn looping_operands_journal_each_cell_once() {
+    let mut longest = 0;
+    let tape = Vm::new().hook(Journal(&mut longest)).run("=2000 ( [0 > + < -] \"==\" ; )").unwrap();
+    assert_eq!(longest, 2);
+    assert_eq!(tape.journal_len(), 0);
+    assert!(tape.current().is(2000));
+}
+
+#[test]
+fn else_if_chains_take_the_first_true_branch() {
+    let chain = "( \"==\" =0 ; +10 . else if \"==\" =1 ; +20 . else if \"<\" =3 ; +30 . else +40 . ) .";
+    for (value, printed) in [(0, "10\n10\n"), (1, "21\n21\n"), (2, "32\n32\n"), (3, "43\n43\n")] {
+        assert_eq!(output(&format!("={} {}", value, chain)).as_deref(), Ok(printed), "cell {}", value);
+    }
+}
+
+#[test]
+fn conditions_nested_in_branches() {
+    let nested = "( \">\" =1 ; ( > \"==\" =2 ; +10 . else +20 . ) else ( \"==\" =0 ; +30 . else +40 . ) )";
+    for (value, printed) in [(0, "30\n"), (1, "41\n"), (3, "13\n")] {
+        assert_eq!(output(&format!("> +2 < ={} {}", value, nested)).as_deref(), Ok(printed), "cell {}", value);
+    }
+    assert_eq!(output(&format!("> +5 < =3 {}", nested)).as_deref(), Ok("23\n"));
+}
+
+#[test]
+fn combinators_skip_operands_that_cannot_change_the_result() {
+    // `/0` stops the program whenever it runs
+    let check = |test: &str, cell| output(&format!("={} ( {} ; +1 . else +2 . )", cell, test));
+    assert_eq!(check("\"==\" =1 \"&&\" /0 \"==\" =0", 0).as_deref(), Ok("2\n"));
+    assert_eq!(check("\"==\" =0 \"||\" /0 \"==\" =0", 0).as_deref(), Ok("1\n"));
+    assert_eq!(check("\"!\" \"==\" =0 \"&&\" /0 \"==\" =0", 0).as_deref(), Ok("2\n"));
+    assert_eq!(check("\"==\" =1 \"&&\" /0 \"==\" =0", 1), Err(RuntimeErrorKind::DivisionByZero));
+    assert_eq!(check("\"==\" =0 \"||\" /0 \"==\" =0", 1), Err(RuntimeErrorKind::DivisionByZero));
+}
+
+#[test]
+fn combinator_precedence() {
+    let check = |test: &str| output(&format!("=0 ( {} ; +1 . else +2 . )", test));
+    // `&&` binds tighter than `||`, true || (false && false) rather than (true || false) && false
+    assert_eq!(check("\"==\" =0 \"||\" \"==\" =1 \"&&\" \"==\" =5").as_deref(), Ok("1\n"));
+    // (false && true) || true
+    assert_eq!(check("\"==\" =1 \"&&\" \"==\" =0 \"||\" \"==\" =0").as_deref(), Ok("1\n"));
+    // `!` only negates the comparison after it, !true || true
+    assert_eq!(check("\"!\" \"==\" =0 \"||\" \"==\" =0").as_deref(), Ok("1\n"));
+    assert_eq!(check("\"!\" \"!\" \"==\" =1 \"&&\" /0 \"==\" =0").as_deref(), Ok("2\n"));
+}
@@ -0,0 +1,41 @@
+use expanded_bf::error::RuntimeErrorKind;
+use expanded_bf::{Error, Vm};
+
+// everything the program printed, or the kind of runtime error that stopped it
+fn output(source: &str, depth_limit: usize) -> Result<String, RuntimeErrorKind> {
+    let mut output = Vec::new();
+    let result = Vm::new().depth_limit(depth_limit).input(&b""[..]).output(&mut output).run(source);
+    match result {
+        Ok(_) => Ok(String::from_utf8(output).unwrap()),
+        Err(Error::Runtime(err)) => Err(err.kind),
+        Err(err) => panic!("'{}' does not run: {}", source, err),
+    }
+}
+
+#[test]
+fn arguments_fill_the_callee_tape_from_cell_0() {
+    let add = "a: 0 b: 1 r: 2 a +3 b +4 {add: > [0 - < + > ] < } &add(a, b) r; r . a . b .";
+    assert_eq!(output(add, 256).as_deref(), Ok("7\n3\n4\n"));
+    // the same cell can be passed twice, the callee gets copies
+    assert_eq!(output("{f: > [0 - < + > ] < } a: 0 a +2 &f(a, a) a; a .", 256).as_deref(), Ok("4\n"));
+    // arguments are code run from the pointer like condition operands
+    assert_eq!(output("{f: } +5 &f(-, +) > ; > .", 256).as_deref(), Ok("4\n"));
+}
+
+#[test]
+fn return_value_is_written_to_the_target() {
+    // the callee ends on its cell 1, the argument is left alone
+    assert_eq!(output("a: 0 r: 1 a +5 {f: *2 > +1 } &f(a) r; r . a .", 256).as_deref(), Ok("1\n5\n"));
+    // no target writes to the cell under the pointer
+    assert_eq!(output("{f: *2 } +5 &f(+) ; .", 256).as_deref(), Ok("12\n"));
+}
+
+#[test]
+fn recursion_stops_at_the_depth_limit() {
+    // counts down and adds one per call on the way back, six calls deep
+    // plus the condition of the last one
+    let down = "{down: ( \"!\" \"==\" =0 ; &down(-) ; +1 ) } a: 0 a +5 &down(a) ; .";
+    assert_eq!(output(down, 7).as_deref(), Ok("5\n"));
+    assert_eq!(output(down, 6), Err(RuntimeErrorKind::RecursionLimit));
+    assert_eq!(output("{f: &f(); } &f();", 256), Err(RuntimeErrorKind::RecursionLimit));
+}
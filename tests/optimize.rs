@@ -0,0 +1,151 @@
+use expanded_bf::compiler::Instruction;
+use expanded_bf::{Cell, CellType, Error, Overflow, Vm};
+
+const CELL_TYPES: [CellType; 7] = [
+    CellType::U8,
+    CellType::U16,
+    CellType::U32,
+    CellType::I32,
+    CellType::I64,
+    CellType::Big,
+    CellType::F64,
+];
+const OVERFLOWS: [Overflow; 3] = [Overflow::Wrap, Overflow::Saturate, Overflow::Error];
+
+// the pointer and every cell that is not 0
+type State = (isize, Vec<(isize, Cell)>);
+
+// the final state or the runtime error and everything printed on the way, errors
+// are compared by kind as a folded run reports the whole run instead of the step,
+// every program has to compile
+fn run(source: &str, optimize: bool, setup: &dyn Fn(Vm) -> Vm) -> (Result<State, String>, String) {
+    let mut output = Vec::new();
+    let result = setup(Vm::new()).optimize(optimize).input(&b""[..]).output(&mut output).run(source);
+    let result = match result {
+        Ok(tape) => {
+            let cells = tape.positions().map(|position| (position, tape.get(position).clone()));
+            Ok((tape.pointer(), cells.filter(|(_, cell)| !cell.is(0)).collect()))
+        }
+        Err(Error::Runtime(err)) => Err(format!("{:?}", err.kind)),
+        Err(err) => panic!("'{}' does not run: {}", source, err),
+    };
+    (result, String::from_utf8(output).unwrap())
+}
+
+fn assert_same_with(source: &str, setup: &dyn Fn(Vm) -> Vm, setting: &str) {
+    let optimized = run(source, true, setup);
+    let unoptimized = run(source, false, setup);
+    assert_eq!(optimized, unoptimized, "'{}' with {}", source, setting);
+}
+
+fn assert_same_in(source: &str, cell_type: CellType, overflow: Overflow) {
+    let setting = format!("{} cells and {:?} overflow", cell_type.name(), overflow);
+    assert_same_with(source, &|vm| vm.cell_type(cell_type).overflow(overflow), &setting);
+}
+
+// with every cell type and overflow policy
+fn assert_same(source: &str) {
+    for cell_type in CELL_TYPES {
+        for overflow in OVERFLOWS {
+            assert_same_in(source, cell_type, overflow);
+        }
+    }
+}
+
+#[test]
+fn folds_runs_and_clear_loops() {
+    let program = expanded_bf::compile("+++-- >>< [0 -] +-", true).unwrap();
+    assert_eq!(
+        program.instructions,
+        [
+            Instruction::Add(1),
+            Instruction::Move { offset: 1, low: 0, high: 2 },
+            Instruction::Clear { until: 0, step: -1 },
+        ]
+    );
+    let program = expanded_bf::compile("+++-- >>< [0 -] +-", false).unwrap();
+    assert_eq!(program.instructions.len(), 13);
+}
+
+#[test]
+fn folded_runs() {
+    assert_same("+++++ --- . >>> << ++ . < .");
+    assert_same("+200 +100 . -50 -250 .");
+    assert_same("+++ >>>> +++++ <<<< .");
+    assert_same("+7 *3 -2 /4 . %3 .");
+    assert_same("+++ [0 > ++ < -] > .");
+    assert_same("=1.5 +++ . --- .");
+}
+
+#[test]
+fn folded_runs_too_large_to_add_up() {
+    assert_same("+9223372036854775807 +9223372036854775807 .");
+    assert_same("-9223372036854775807 -9223372036854775807 .");
+    assert_same("> *@>9223372036854775807");
+}
+
+#[test]
+fn cancelled_pairs() {
+    assert_same("+- . -+ . +-+-+ .");
+    assert_same("+255 +- . -+ .");
+    assert_same("-+-+ . +-+- .");
+    assert_same("> <> >< >><< + . >< .");
+    assert_same("<> + .");
+    assert_same("> + [0 -+ - <> ] .");
+}
+
+#[test]
+fn cancelled_moves_at_the_ends_of_the_tape() {
+    for source in ["<> + .", ">>> <<<<< >> + .", "> >< + .", ">>>> <<<< + ."] {
+        assert_same_with(source, &|vm| vm.bidirectional(true), "a bidirectional tape");
+        assert_same_with(source, &|vm| vm.tape_limit(Some(3)), "a tape of 3 cells");
+        assert_same_with(source, &|vm| vm.bidirectional(true).tape_limit(Some(3)), "both");
+    }
+}
+
+#[test]
+fn clear_loops() {
+    assert_same("+++ [0 -] . [0 -] .");
+    assert_same("---- [0 +] .");
+    assert_same("+++++ [2 -] . [7 +] .");
+    assert_same("+255 [0 -] .");
+    assert_same("=2.5 *2 [0 -] .");
+    for cell_type in [CellType::I32, CellType::I64, CellType::Big, CellType::F64] {
+        for overflow in OVERFLOWS {
+            assert_same_in("-3 [0 +] . -5 [2 +] . [0 -] .", cell_type, overflow);
+        }
+    }
+}
+
+#[test]
+fn clear_loops_on_big_cells() {
+    for overflow in OVERFLOWS {
+        assert_same_in("+9223372036854775807 +10 [9223372036854775807 -] .", CellType::Big, overflow);
+    }
+    // far too many steps to take one at a time
+    let source = "+9223372036854775807 +10 [0 -] .";
+    let (result, output) = run(source, true, &|vm| vm.cell_type(CellType::Big));
+    assert_eq!(result, Ok((0, vec![])));
+    assert_eq!(output, run("[0 -] .", true, &|vm| vm.cell_type(CellType::Big)).1);
+}
+
+#[test]
+fn clear_loops_that_wrap() {
+    for overflow in [Overflow::Wrap, Overflow::Error] {
+        assert_same_in("+++ [0 +] .", CellType::U8, overflow);
+        assert_same_in("+++ [0 +] .", CellType::U16, overflow);
+        assert_same_in("[250 -] .", CellType::U8, overflow);
+    }
+}
+
+#[test]
+fn clear_loops_past_the_cell_type() {
+    assert_same_in("[300 +] .", CellType::U8, Overflow::Error);
+    assert_same_in("+5 [3 +] .", CellType::U8, Overflow::Error);
+    assert_same_in("[70000 +] .", CellType::U16, Overflow::Error);
+}
+
+#[test]
+fn main_program() {
+    assert_same(include_str!("../main.bf"));
+}